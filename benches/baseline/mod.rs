@@ -0,0 +1,100 @@
+//! The encoder's hot path as it was before the writer was redesigned, kept
+//! as the baseline for the benchmark: a global CRC behind a Mutex, updated a
+//! byte at a time with the nibble CRC, and two unbuffered `write_all` calls
+//! per message straight to the `File`.
+//!
+//! Like the old encoder, the file CRC covers the record area only and the
+//! header is patched in place once the file is complete.
+
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::sync::Mutex;
+
+use echo2garmin::{Decoder, Record};
+
+const HEADER_SIZE: u64 = 14;
+
+static DATA_CRC: Mutex<u16> = Mutex::new(0);
+
+fn write_file_header(file: &mut File) {
+    let file_size = file.seek(SeekFrom::End(0)).unwrap();
+    let data_size = file_size.saturating_sub(HEADER_SIZE + 2) as u32;
+
+    let mut header = vec![HEADER_SIZE as u8, 0x20];
+    header.extend_from_slice(&21158u16.to_le_bytes());
+    header.extend_from_slice(&data_size.to_le_bytes());
+    header.extend_from_slice(b".FIT");
+    let crc = crc_calc16(&header);
+    header.extend_from_slice(&crc.to_le_bytes());
+
+    file.seek(SeekFrom::Start(0)).unwrap();
+    file.write_all(&header).unwrap();
+}
+
+fn write_message_definition(file: &mut File, local_mesg_number: u8, mesg_def: &[u8]) {
+    write_data(file, &[local_mesg_number | 0x40]);
+    write_data(file, mesg_def);
+}
+
+fn write_message(file: &mut File, local_mesg_number: u8, message: &[u8]) {
+    write_data(file, &[local_mesg_number]);
+    write_data(file, message);
+}
+
+fn write_data(file: &mut File, data: &[u8]) {
+    file.write_all(data).unwrap();
+    for &byte in data {
+        let crc = get_crc();
+        set_crc(crc_get16(crc, byte));
+    }
+}
+
+fn get_crc() -> u16 {
+    *DATA_CRC.lock().unwrap()
+}
+
+fn set_crc(crc: u16) {
+    *DATA_CRC.lock().unwrap() = crc;
+}
+
+// The CRC-16 from the FIT protocol description, two 16-entry table lookups
+// per byte.
+pub fn crc_get16(mut crc: u16, byte: u8) -> u16 {
+    const CRC_TABLE: [u16; 16] = [
+        0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401,
+        0xA001, 0x6C00, 0x7800, 0xB401, 0x5000, 0x9C01, 0x8801, 0x4400,
+    ];
+
+    let tmp = CRC_TABLE[(crc & 0xF) as usize];
+    crc = ((crc >> 4) & 0x0FFF) ^ tmp ^ CRC_TABLE[(byte & 0xF) as usize];
+    let tmp = CRC_TABLE[(crc & 0xF) as usize];
+    ((crc >> 4) & 0x0FFF) ^ tmp ^ CRC_TABLE[((byte >> 4) & 0xF) as usize]
+}
+
+pub fn crc_calc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &byte| crc_get16(crc, byte))
+}
+
+/// Writes the messages of an encoded file to `file` through the old path,
+/// one message at a time, and returns the size of the result.
+pub fn write_file(data: &[u8], file: &mut File) -> u64 {
+    set_crc(0);
+    write_file_header(file);
+
+    let mut decoder = Decoder::new(data).unwrap();
+    let mut start = decoder.offset();
+    while let Some(record) = decoder.next_record().unwrap() {
+        let end = decoder.offset();
+        // Keeps the developer data flag; the old functions set the rest.
+        let local_mesg_number = data[start] & !0x40;
+        match record {
+            Record::Definition(_) => write_message_definition(file, local_mesg_number, &data[start + 1..end]),
+            Record::Data(_) => write_message(file, local_mesg_number, &data[start + 1..end]),
+        }
+        start = end;
+    }
+
+    file.write_all(&get_crc().to_le_bytes()).unwrap();
+    write_file_header(file);
+    file.seek(SeekFrom::End(0)).unwrap()
+}
@@ -0,0 +1,109 @@
+//! Encoding throughput, e.g. for re-encoding a season of rides.
+//!
+//! Run with `cargo bench`. Writes a long activity of one-second records with
+//! Echo developer fields to a temporary file and to memory, and reports
+//! records and megabytes per second for each.
+//!
+//! The baseline is the old encoder in `baseline`, which writes the same
+//! messages to a file with a Mutex-guarded nibble CRC per byte and two
+//! unbuffered `write_all` calls per message. It is handed the messages
+//! already encoded, so it is timed on its I/O and CRC alone and the gain
+//! it shows is if anything understated.
+
+use std::env;
+use std::fs::{self, File};
+use std::hint::black_box;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use echo2garmin::{
+    write_echo_developer_data, write_file_id_message, write_mesg, DeveloperField, EchoMetric, FitError, FitWriter,
+    RecordMesg,
+};
+
+mod baseline;
+
+// The crate's CRC is internal; the bench builds its own copy to time it.
+#[allow(dead_code)]
+#[path = "../src/crc.rs"]
+mod crc;
+
+const RECORDS: u32 = 200_000;
+const RUNS: u32 = 5;
+
+fn write_activity<W: Write>(fit: &mut FitWriter<W>) -> Result<(), FitError> {
+    write_file_id_message(fit)?;
+    write_echo_developer_data(fit)?;
+
+    let mut record = RecordMesg::default();
+    for second in 0..RECORDS {
+        record.timestamp = 1_000_000_000 + second;
+        record.heart_rate = 120 + (second % 40) as u8;
+        record.cadence = 80 + (second % 20) as u8;
+        record.power = 200 + (second % 150) as u16;
+        record.set_speed_saturating(8.0 + (second % 10) as f64 * 0.1);
+        record.set_distance_saturating(second as f64 * 8.5);
+        let developer_fields = [
+            DeveloperField::try_from(EchoMetric::FanRpm(900 + (second % 300) as u16))?,
+            DeveloperField::try_from(EchoMetric::ConsoleCalories((second / 60) as u16))?,
+        ];
+        write_mesg(fit, &record, &developer_fields)?;
+    }
+    Ok(())
+}
+
+fn report(name: &str, elapsed: Duration, bytes: u64) {
+    let seconds = elapsed.as_secs_f64();
+    println!(
+        "{name:<16} {:>10.0} records/s {:>8.1} MB/s ({bytes} bytes in {:.1} ms)",
+        RECORDS as f64 / seconds,
+        bytes as f64 / seconds / 1e6,
+        seconds * 1e3,
+    );
+}
+
+// Best of RUNS, to keep scheduling noise out of the numbers.
+fn bench(name: &str, mut run: impl FnMut() -> u64) {
+    let mut best = Duration::MAX;
+    let mut bytes = 0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        bytes = black_box(run());
+        best = best.min(start.elapsed());
+    }
+    report(name, best, bytes);
+}
+
+fn main() {
+    let path = env::temp_dir().join(format!("echo2garmin-bench-{}.fit", std::process::id()));
+
+    bench("file", || {
+        let mut fit = FitWriter::new(File::create(&path).unwrap()).unwrap();
+        write_activity(&mut fit).unwrap();
+        fit.finish().unwrap().metadata().unwrap().len()
+    });
+
+    let mut fit = FitWriter::buffered(Vec::new());
+    write_activity(&mut fit).unwrap();
+    let data = fit.finish().unwrap();
+    bench("file, baseline", || baseline::write_file(&data, &mut File::create(&path).unwrap()));
+
+    bench("memory", || {
+        let mut fit = FitWriter::buffered(Vec::new());
+        write_activity(&mut fit).unwrap();
+        fit.finish().unwrap().len() as u64
+    });
+
+    fs::remove_file(&path).unwrap();
+
+    // The CRC alone, over the bytes of the same activity.
+    assert_eq!(crc::crc_calc16(&data), baseline::crc_calc16(&data));
+    bench("crc, table", || {
+        black_box(crc::crc_calc16(black_box(&data)));
+        data.len() as u64
+    });
+    bench("crc, nibbles", || {
+        black_box(baseline::crc_calc16(black_box(&data)));
+        data.len() as u64
+    });
+}
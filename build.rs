@@ -0,0 +1,436 @@
+// Generates the message structs, field tables and enums included by
+// `src/profile.rs` from the CSV export of the FIT profile in `profile/`.
+//
+// The export is a subset of Profile.xlsx: the messages this crate writes or
+// reads back, and the types their fields refer to. Rows are copied from the
+// SDK unchanged, so further messages can be pasted in as they are needed.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+const TYPES_CSV: &str = "profile/types.csv";
+const MESSAGES_CSV: &str = "profile/messages.csv";
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "static", "struct",
+    "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+];
+
+struct ProfileType {
+    name: String,
+    base_type: String,
+    values: Vec<(String, u64, String)>,
+}
+
+struct ProfileField {
+    num: u8,
+    name: String,
+    profile_type: String,
+    array: Option<usize>,
+    scale: String,
+    offset: String,
+    units: String,
+    comment: String,
+    components: Vec<ProfileComponent>,
+}
+
+// One value packed into a component field, in the order the profile lists
+// them, which is from the low bits up.
+struct ProfileComponent {
+    name: String,
+    scale: String,
+    offset: String,
+    units: String,
+    bits: u32,
+    accumulate: bool,
+}
+
+struct ProfileMesg {
+    name: String,
+    fields: Vec<ProfileField>,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={TYPES_CSV}");
+    println!("cargo:rerun-if-changed={MESSAGES_CSV}");
+
+    let types = parse_types(&read_csv(TYPES_CSV));
+    let mesgs = parse_messages(&read_csv(MESSAGES_CSV));
+
+    let mut out = String::new();
+    out.push_str("// @generated by build.rs from profile/*.csv. Do not edit.\n\n");
+    let mut enums: Vec<&ProfileType> = types.values().filter(|t| !t.values.is_empty()).collect();
+    enums.sort_by(|a, b| a.name.cmp(&b.name));
+    for profile_type in &enums {
+        write_enum(&mut out, profile_type);
+    }
+    write_enum_value_name(&mut out, &enums);
+
+    let mesg_nums = &types.get("mesg_num").expect("profile has no mesg_num type").values;
+    let mut generated = Vec::new();
+    for mesg in &mesgs {
+        let Some((_, mesg_num, _)) = mesg_nums.iter().find(|(name, _, _)| *name == mesg.name) else {
+            println!("cargo:warning=skipping message {}: not listed in mesg_num", mesg.name);
+            continue;
+        };
+        write_mesg(&mut out, mesg, *mesg_num, &types);
+        generated.push(mesg);
+    }
+    write_mesg_profile(&mut out, &generated);
+
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("profile.rs");
+    fs::write(path, out).unwrap();
+}
+
+// Minimal CSV reader: comma separated, double-quoted cells may hold commas
+// and "" escapes. The header row is dropped.
+fn read_csv(path: &str) -> Vec<Vec<String>> {
+    let text = fs::read_to_string(path).unwrap_or_else(|e| panic!("{path}: {e}"));
+    let mut rows = Vec::new();
+    for line in text.lines().skip(1) {
+        let mut row = Vec::new();
+        let mut cell = String::new();
+        let mut quoted = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    cell.push('"');
+                    chars.next();
+                }
+                '"' => quoted = !quoted,
+                ',' if !quoted => row.push(std::mem::take(&mut cell)),
+                _ => cell.push(c),
+            }
+        }
+        row.push(cell);
+        rows.push(row);
+    }
+    rows
+}
+
+fn cell(row: &[String], index: usize) -> &str {
+    row.get(index).map(|s| s.trim()).unwrap_or("")
+}
+
+fn parse_number(value: &str) -> u64 {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .unwrap_or_else(|_| panic!("bad value {value:?} in {TYPES_CSV}"))
+}
+
+// Columns: Type Name, Base Type, Value Name, Value, Comment.
+fn parse_types(rows: &[Vec<String>]) -> HashMap<String, ProfileType> {
+    let mut types: Vec<ProfileType> = Vec::new();
+    for row in rows {
+        if !cell(row, 0).is_empty() {
+            types.push(ProfileType { name: cell(row, 0).to_string(), base_type: cell(row, 1).to_string(), values: Vec::new() });
+        } else if !cell(row, 2).is_empty() {
+            let profile_type = types.last_mut().expect("type value before any type");
+            profile_type.values.push((cell(row, 2).to_string(), parse_number(cell(row, 3)), cell(row, 4).to_string()));
+        }
+    }
+    types.into_iter().map(|t| (t.name.clone(), t)).collect()
+}
+
+// Columns: Message Name, Field Def #, Field Name, Field Type, Array,
+// Components, Scale, Offset, Units, Bits, Accumulate, Ref Field Name,
+// Ref Field Value, Comment. Subfield rows have no field number and are
+// left out.
+fn parse_messages(rows: &[Vec<String>]) -> Vec<ProfileMesg> {
+    let mut mesgs: Vec<ProfileMesg> = Vec::new();
+    for row in rows {
+        if !cell(row, 0).is_empty() {
+            mesgs.push(ProfileMesg { name: cell(row, 0).to_string(), fields: Vec::new() });
+            continue;
+        }
+        let Ok(num) = cell(row, 1).parse::<u8>() else {
+            continue;
+        };
+        let mesg = mesgs.last_mut().expect("field before any message");
+        let name = cell(row, 2).to_string();
+        let array = match cell(row, 4).trim_start_matches('[').trim_end_matches(']') {
+            "" => None,
+            // Strings are sized by their contents.
+            _ if cell(row, 3) == "string" => None,
+            size => match size.parse() {
+                Ok(size) => Some(size),
+                Err(_) => {
+                    println!("cargo:warning=skipping {}.{name}: array size {size} is not fixed", mesg.name);
+                    continue;
+                }
+            },
+        };
+        // A field made of several components lists a scale, offset, unit,
+        // width and accumulate flag per component, and is itself written raw.
+        // A single component shares its scale with the field.
+        let list = |index| cell(row, index).split(',').map(|value| value.trim().to_string()).collect::<Vec<_>>();
+        let (scales, offsets, units, bits, accumulate) = (list(6), list(7), list(8), list(9), list(10));
+        let nth = |values: &[String], i: usize| values.get(i).cloned().unwrap_or_default();
+        let components: Vec<ProfileComponent> = list(5)
+            .into_iter()
+            .filter(|name| !name.is_empty())
+            .enumerate()
+            .map(|(i, component)| ProfileComponent {
+                scale: nth(&scales, i),
+                offset: nth(&offsets, i),
+                units: nth(&units, i),
+                bits: nth(&bits, i)
+                    .parse()
+                    .unwrap_or_else(|_| panic!("{}.{name}: component {component} has no bit width", mesg.name)),
+                accumulate: nth(&accumulate, i) == "1",
+                name: component,
+            })
+            .collect();
+        let field_level = |index| if components.len() > 1 { String::new() } else { cell(row, index).to_string() };
+        mesg.fields.push(ProfileField {
+            num,
+            name,
+            profile_type: cell(row, 3).to_string(),
+            array,
+            scale: field_level(6),
+            offset: field_level(7),
+            units: field_level(8),
+            comment: cell(row, 13).to_string(),
+            components,
+        });
+    }
+    mesgs
+}
+
+fn camel_case(name: &str) -> String {
+    let mut camel: String = name
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|c| c.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+        })
+        .collect();
+    if camel.starts_with(|c: char| c.is_ascii_digit()) {
+        camel.insert(0, '_');
+    }
+    camel
+}
+
+fn field_ident(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("{name}_")
+    } else {
+        name.to_string()
+    }
+}
+
+// Base type constant and Rust type for a base type name.
+fn base_type(name: &str) -> Option<(&'static str, &'static str)> {
+    Some(match name {
+        "enum" => ("BASE_TYPE_ENUM", "u8"),
+        "sint8" => ("BASE_TYPE_SINT8", "i8"),
+        "uint8" => ("BASE_TYPE_UINT8", "u8"),
+        "sint16" => ("BASE_TYPE_SINT16", "i16"),
+        "uint16" => ("BASE_TYPE_UINT16", "u16"),
+        "sint32" => ("BASE_TYPE_SINT32", "i32"),
+        "uint32" => ("BASE_TYPE_UINT32", "u32"),
+        "string" => ("BASE_TYPE_STRING", "u8"),
+        "uint8z" => ("BASE_TYPE_UINT8Z", "u8"),
+        "uint16z" => ("BASE_TYPE_UINT16Z", "u16"),
+        "uint32z" => ("BASE_TYPE_UINT32Z", "u32"),
+        "byte" => ("BASE_TYPE_BYTE", "u8"),
+        "float32" => ("BASE_TYPE_FLOAT32", "f32"),
+        "float64" => ("BASE_TYPE_FLOAT64", "f64"),
+        "sint64" => ("BASE_TYPE_SINT64", "i64"),
+        "uint64" => ("BASE_TYPE_UINT64", "u64"),
+        "uint64z" => ("BASE_TYPE_UINT64Z", "u64"),
+        _ => return None,
+    })
+}
+
+fn resolve_base_type<'a>(types: &'a HashMap<String, ProfileType>, profile_type: &'a str) -> &'a str {
+    types.get(profile_type).map(|t| t.base_type.as_str()).unwrap_or(profile_type)
+}
+
+fn doc(out: &mut String, indent: &str, comment: &str) {
+    if !comment.is_empty() {
+        writeln!(out, "{indent}#[doc = {comment:?}]").unwrap();
+    }
+}
+
+fn write_enum(out: &mut String, profile_type: &ProfileType) {
+    let name = camel_case(&profile_type.name);
+    let (_, repr) = base_type(&profile_type.base_type)
+        .unwrap_or_else(|| panic!("type {} has unknown base type {}", profile_type.name, profile_type.base_type));
+
+    writeln!(out, "/// Values of the `{}` profile type.", profile_type.name).unwrap();
+    writeln!(out, "#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]").unwrap();
+    writeln!(out, "#[repr({repr})]").unwrap();
+    writeln!(out, "pub enum {name} {{").unwrap();
+    for (value_name, value, comment) in &profile_type.values {
+        doc(out, "    ", comment);
+        writeln!(out, "    {} = {value},", camel_case(value_name)).unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+
+    writeln!(out, "impl {name} {{").unwrap();
+    writeln!(out, "    pub fn name(self) -> &'static str {{").unwrap();
+    writeln!(out, "        match self {{").unwrap();
+    for (value_name, _, _) in &profile_type.values {
+        writeln!(out, "            {name}::{} => {value_name:?},", camel_case(value_name)).unwrap();
+    }
+    writeln!(out, "        }}\n    }}\n}}\n").unwrap();
+
+    writeln!(out, "impl From<{name}> for {repr} {{").unwrap();
+    writeln!(out, "    fn from(value: {name}) -> {repr} {{\n        value as {repr}\n    }}\n}}\n").unwrap();
+
+    writeln!(out, "impl TryFrom<{repr}> for {name} {{").unwrap();
+    writeln!(out, "    type Error = {repr};\n").unwrap();
+    writeln!(out, "    fn try_from(value: {repr}) -> Result<Self, {repr}> {{").unwrap();
+    writeln!(out, "        match value {{").unwrap();
+    for (value_name, value, _) in &profile_type.values {
+        writeln!(out, "            {value} => Ok({name}::{}),", camel_case(value_name)).unwrap();
+    }
+    writeln!(out, "            _ => Err(value),\n        }}\n    }}\n}}\n").unwrap();
+}
+
+fn write_enum_value_name(out: &mut String, enums: &[&ProfileType]) {
+    writeln!(out, "/// Name of `value` in the enum behind `profile_type`, e.g. `\"cycling\"`").unwrap();
+    writeln!(out, "/// for `(\"sport\", 2)`.").unwrap();
+    writeln!(out, "pub fn enum_value_name(profile_type: &str, value: u32) -> Option<&'static str> {{").unwrap();
+    writeln!(out, "    match profile_type {{").unwrap();
+    for profile_type in enums {
+        let (_, repr) = base_type(&profile_type.base_type).unwrap();
+        writeln!(
+            out,
+            "        {:?} => {repr}::try_from(value).ok().and_then(|v| {}::try_from(v).ok()).map({}::name),",
+            profile_type.name,
+            camel_case(&profile_type.name),
+            camel_case(&profile_type.name),
+        )
+        .unwrap();
+    }
+    writeln!(out, "        _ => None,\n    }}\n}}\n").unwrap();
+}
+
+fn write_mesg(out: &mut String, mesg: &ProfileMesg, mesg_num: u64, types: &HashMap<String, ProfileType>) {
+    // Setters and component tables, for the message's impl block.
+    let mut items = String::new();
+    writeln!(out, "fit_message! {{").unwrap();
+    writeln!(out, "    /// The `{}` message.", mesg.name).unwrap();
+    writeln!(out, "    pub struct {}Mesg: {mesg_num} {{", camel_case(&mesg.name)).unwrap();
+    for field in &mesg.fields {
+        let base_name = resolve_base_type(types, &field.profile_type);
+        let Some((base_const, element)) = base_type(base_name) else {
+            println!("cargo:warning=skipping {}.{}: unsupported base type {base_name}", mesg.name, field.name);
+            continue;
+        };
+        let ty = match field.array {
+            _ if base_name == "string" => "FitString".to_string(),
+            Some(size) if element == "u8" => format!("[u8; {size}]"),
+            None => element.to_string(),
+            _ => {
+                println!("cargo:warning=skipping {}.{}: arrays need a fixed size and a byte base type", mesg.name, field.name);
+                continue;
+            }
+        };
+        let scale = if field.scale.is_empty() { "1" } else { &field.scale };
+        let offset = if field.offset.is_empty() { "0" } else { &field.offset };
+        doc(out, "        ", &field.comment);
+        writeln!(
+            out,
+            "        {}: {ty} = ({}, {:?}, {:?}, {base_const}, {scale}, {offset}, {:?}),",
+            field_ident(&field.name),
+            field.num,
+            field.name,
+            field.profile_type,
+            field.units,
+        )
+        .unwrap();
+
+        // Fields holding a physical quantity get setters that take it in
+        // the field's units and do the scaling.
+        if field.array.is_none() && base_type(&field.profile_type).is_some() && base_name != "string" && base_name != "byte"
+            && (!field.units.is_empty() || scale != "1" || offset != "0")
+        {
+            write_setter(&mut items, field, base_const, scale, offset);
+        }
+        if !field.components.is_empty() {
+            write_components(&mut items, mesg, field);
+        }
+    }
+    writeln!(out, "    }}\n}}\n").unwrap();
+
+    if !items.is_empty() {
+        writeln!(out, "impl {}Mesg {{{items}}}\n", camel_case(&mesg.name)).unwrap();
+    }
+}
+
+fn write_components(out: &mut String, mesg: &ProfileMesg, field: &ProfileField) {
+    writeln!(out).unwrap();
+    writeln!(out, "    /// Components packed into `{}`, from the low bits up.", field.name).unwrap();
+    writeln!(out, "    pub const {}_COMPONENTS: &[Component] = &[", field.name.to_uppercase()).unwrap();
+    for component in &field.components {
+        let target = mesg.fields.iter().find(|target| target.name == component.name);
+        let target = target.unwrap_or_else(|| panic!("{}.{}: component {} is not a field", mesg.name, field.name, component.name));
+        let scale: f64 = if component.scale.is_empty() { 1.0 } else { component.scale.parse().unwrap() };
+        let offset: f64 = if component.offset.is_empty() { 0.0 } else { component.offset.parse().unwrap() };
+        writeln!(
+            out,
+            "        Component {{ field_num: {}, name: {:?}, scale: {scale:?}, offset: {offset:?}, units: {:?}, bits: {}, accumulate: {} }},",
+            target.num, component.name, component.units, component.bits, component.accumulate,
+        )
+        .unwrap();
+    }
+    writeln!(out, "    ];").unwrap();
+}
+
+fn write_setter(out: &mut String, field: &ProfileField, base_const: &str, scale: &str, offset: &str) {
+    let ident = field_ident(&field.name);
+    let units = if field.units.is_empty() { String::new() } else { format!(" in {}", field.units) };
+    let scale: f64 = scale.parse().unwrap();
+    let offset: f64 = offset.parse().unwrap();
+    let args = format!("value, {base_const}, {scale:?}, {offset:?}");
+    writeln!(out).unwrap();
+    writeln!(out, "    /// Sets `{}` from a value{units}, failing if it does not fit.", field.name).unwrap();
+    writeln!(out, "    pub fn set_{}(&mut self, value: f64) -> Result<(), FieldRangeError> {{", field.name).unwrap();
+    writeln!(out, "        self.{ident} = to_raw({:?}, {args})?;", field.name).unwrap();
+    writeln!(out, "        Ok(())\n    }}\n").unwrap();
+    writeln!(out, "    /// Sets `{}` from a value{units}, clamped to the field's range.", field.name).unwrap();
+    writeln!(out, "    pub fn set_{}_saturating(&mut self, value: f64) {{", field.name).unwrap();
+    writeln!(out, "        self.{ident} = to_raw_saturating({args});\n    }}").unwrap();
+}
+
+fn write_mesg_profile(out: &mut String, mesgs: &[&ProfileMesg]) {
+    writeln!(out, "/// Name and field table of a message in the bundled profile.").unwrap();
+    writeln!(out, "pub fn mesg_profile(mesg_num: u16) -> Option<(&'static str, &'static [Field])> {{").unwrap();
+    writeln!(out, "    match mesg_num {{").unwrap();
+    for mesg in mesgs {
+        let name = camel_case(&mesg.name);
+        writeln!(out, "        {name}Mesg::MESG_NUM => Some(({:?}, {name}Mesg::FIELDS)),", mesg.name).unwrap();
+    }
+    writeln!(out, "        _ => None,\n    }}\n}}\n").unwrap();
+
+    writeln!(out, "/// A message of any type in the bundled profile.").unwrap();
+    writeln!(out, "#[derive(Clone, Debug, PartialEq)]").unwrap();
+    writeln!(out, "pub enum Message {{").unwrap();
+    for mesg in mesgs {
+        let name = camel_case(&mesg.name);
+        writeln!(out, "    {name}({name}Mesg),").unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+
+    writeln!(out, "impl Message {{").unwrap();
+    writeln!(out, "    /// Builds the message numbered `mesg_num` from (field number,").unwrap();
+    writeln!(out, "    /// little-endian value) pairs, or None if the profile lacks it.").unwrap();
+    writeln!(out, "    pub fn from_fields<'a>(mesg_num: u16, fields: impl IntoIterator<Item = (u8, &'a [u8])>) -> Option<Message> {{").unwrap();
+    writeln!(out, "        Some(match mesg_num {{").unwrap();
+    for mesg in mesgs {
+        let name = camel_case(&mesg.name);
+        writeln!(out, "            {name}Mesg::MESG_NUM => Message::{name}(mesg_from_fields(fields)),").unwrap();
+    }
+    writeln!(out, "            _ => return None,\n        }})\n    }}\n}}").unwrap();
+}
@@ -0,0 +1,19 @@
+#![no_main]
+
+use echo2garmin::{dump, Decoder, DumpFormat, Record};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Walk the records one at a time, as well as through the typed
+    // messages and both dump formats.
+    if let Ok(decoder) = Decoder::new(data) {
+        for record in decoder {
+            if let Ok(Record::Data(mesg)) = record {
+                let _ = mesg.to_message();
+            }
+        }
+    }
+    let _ = echo2garmin::decode(data);
+    let _ = dump(data, DumpFormat::Text, &mut std::io::sink());
+    let _ = dump(data, DumpFormat::Json, &mut std::io::sink());
+});
@@ -0,0 +1,17 @@
+#![no_main]
+
+use echo2garmin::{repair, validate};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let issues = validate(data);
+    for issue in &issues {
+        let _ = issue.to_string();
+    }
+
+    // Whatever repair salvages must come out valid as far as structure and
+    // CRCs go.
+    if let Ok(repaired) = repair(data) {
+        echo2garmin::decode(&repaired.data).unwrap();
+    }
+});
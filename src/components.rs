@@ -0,0 +1,81 @@
+use crate::profile::RecordMesg;
+use crate::schema::Component;
+
+/// Running total of an accumulated field such as power or distance.
+///
+/// FIT stores accumulated fields as the low bits of the total, which readers
+/// unwrap by adding the difference from the previous value modulo the field
+/// width. That only works if the total never grows by a full rollover
+/// between two messages carrying it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Accumulator {
+    total: u64,
+}
+
+impl Accumulator {
+    pub fn add(&mut self, value: u64) {
+        self.total = self.total.wrapping_add(value);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The total as a field `bits` wide sees it.
+    ///
+    /// # Panics
+    ///
+    /// If `bits` is not between 1 and 64.
+    pub fn rolled(&self, bits: u32) -> u64 {
+        self.total & mask(bits)
+    }
+}
+
+fn mask(bits: u32) -> u64 {
+    assert!((1..=64).contains(&bits), "a field cannot be {bits} bits wide");
+    u64::MAX >> (64 - bits)
+}
+
+// Packs values in the components' units into their bit fields, low bits
+// first. Accumulated components roll over at their width. The others
+// saturate one below it, since a component with every bit set reads as
+// invalid; that also keeps the packed field off its all-ones invalid value.
+fn pack(components: &[Component], values: &[f64]) -> u64 {
+    let mut packed = 0;
+    let mut shift = 0;
+    for (component, value) in components.iter().zip(values) {
+        let raw = ((value + component.offset) * component.scale).round().max(0.0) as u64;
+        let raw = if component.accumulate { raw & mask(component.bits) } else { raw.min(mask(component.bits) - 1) };
+        packed |= raw << shift;
+        shift += component.bits;
+    }
+    packed
+}
+
+/// Packs speed in m/s and the total distance in metres into the three byte
+/// `compressed_speed_distance` form. Speed saturates at 40.94 m/s; distance
+/// rolls over every 256 m, so it has to be sent at least that often.
+pub fn compress_speed_distance(speed: f64, distance: f64) -> [u8; 3] {
+    let packed = pack(RecordMesg::COMPRESSED_SPEED_DISTANCE_COMPONENTS, &[speed, distance]);
+    let bytes = packed.to_le_bytes();
+    [bytes[0], bytes[1], bytes[2]]
+}
+
+impl RecordMesg {
+    /// Sets `compressed_speed_distance` from m/s and metres travelled so far.
+    pub fn set_compressed_speed_distance(&mut self, speed: f64, distance: f64) {
+        self.compressed_speed_distance = compress_speed_distance(speed, distance);
+    }
+
+    /// Sets `compressed_accumulated_power` from the running power total in
+    /// watts, rolled over at 16 bits.
+    ///
+    /// A rolled total of 0xFFFF is the field's invalid value, so the record
+    /// goes out without it. Readers take the total up again from the next
+    /// record that carries it, as long as it has not grown by another 65536 W
+    /// in between.
+    pub fn set_compressed_power(&mut self, accumulated_power: &Accumulator) {
+        let bits = RecordMesg::COMPRESSED_ACCUMULATED_POWER_COMPONENTS[0].bits;
+        self.compressed_accumulated_power = accumulated_power.rolled(bits) as u16;
+    }
+}
@@ -0,0 +1,73 @@
+// CRC-16 as used by FIT (polynomial 0x8005, reflected, initial value 0),
+// one table lookup per byte.
+const CRC_TABLE: [u16; 256] = crc_table();
+
+const fn crc_table() -> [u16; 256] {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte as u16;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xA001 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+}
+
+pub(crate) fn crc_get16(crc: u16, byte: u8) -> u16 {
+    (crc >> 8) ^ CRC_TABLE[((crc ^ byte as u16) & 0xFF) as usize]
+}
+
+pub(crate) fn crc_update16(mut crc: u16, data: &[u8]) -> u16 {
+    for &byte in data {
+        crc = crc_get16(crc, byte);
+    }
+    crc
+}
+
+pub(crate) fn crc_calc16(data: &[u8]) -> u16 {
+    crc_update16(0, data)
+}
+
+// CRC of `a` followed by `b`, from the CRCs of each and the length of `b`.
+// The CRC is linear, so `crc_a` multiplied by x^(8 * len_b) modulo the
+// polynomial, which is what running it through `len_b` zero bytes does,
+// folded in with `crc_b` gives the same result as processing `b` after `a`.
+// The power is built by repeated squaring, so this takes O(log len_b).
+pub(crate) fn crc_combine16(crc_a: u16, crc_b: u16, len_b: u64) -> u16 {
+    let mut shift = X_POW_0;
+    let mut square = X_POW_8;
+    let mut len = len_b;
+    while len != 0 {
+        if len & 1 != 0 {
+            shift = multmodp(shift, square);
+        }
+        square = multmodp(square, square);
+        len >>= 1;
+    }
+    multmodp(shift, crc_a) ^ crc_b
+}
+
+// Polynomials modulo the CRC polynomial, in the same reflected form as the
+// CRC register: x^0 is the top bit and x^15 the bottom one.
+const X_POW_0: u16 = 1 << 15;
+const X_POW_8: u16 = 1 << 7;
+
+// a * b modulo the CRC polynomial.
+fn multmodp(a: u16, mut b: u16) -> u16 {
+    let mut product = 0;
+    let mut m = X_POW_0;
+    while m != 0 {
+        if a & m != 0 {
+            product ^= b;
+        }
+        // b times x.
+        b = if b & 1 != 0 { (b >> 1) ^ 0xA001 } else { b >> 1 };
+        m >>= 1;
+    }
+    product
+}
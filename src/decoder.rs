@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::crc::crc_calc16;
+use crate::header::HeaderSize;
+use crate::messages::DeveloperField;
+use crate::profile::{FieldDescriptionMesg, Message};
+use crate::schema::{mesg_from_fields, swap_byte_order, Mesg, BASE_TYPE_UINT32};
+
+const LOCAL_MESG_COUNT: usize = 16;
+const TIMESTAMP_FIELD_NUM: u8 = 253;
+const FILE_CRC_SIZE: usize = 2;
+
+/// What went wrong while reading a FIT file, and at which byte offset.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The header is too short, has an unknown size or lacks the ".FIT"
+    /// signature.
+    InvalidHeader,
+    HeaderCrcMismatch { expected: u16, actual: u16 },
+    /// The file ends before the record area or the CRC does.
+    UnexpectedEof { offset: usize },
+    MalformedDefinition { offset: usize },
+    /// A data message uses a local message type that has no definition.
+    UndefinedLocalMesg { offset: usize, local_mesg_number: u8 },
+    /// A compressed timestamp header comes before any full timestamp.
+    MissingTimestamp { offset: usize },
+    CrcMismatch { expected: u16, actual: u16 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidHeader => f.write_str("not a FIT file header"),
+            DecodeError::HeaderCrcMismatch { expected, actual } => {
+                write!(f, "header CRC is {actual:#06x}, expected {expected:#06x}")
+            }
+            DecodeError::UnexpectedEof { offset } => write!(f, "file ends unexpectedly at byte {offset}"),
+            DecodeError::MalformedDefinition { offset } => write!(f, "malformed definition message at byte {offset}"),
+            DecodeError::UndefinedLocalMesg { offset, local_mesg_number } => {
+                write!(f, "local message type {local_mesg_number} used before its definition at byte {offset}")
+            }
+            DecodeError::MissingTimestamp { offset } => {
+                write!(f, "compressed timestamp without a previous timestamp at byte {offset}")
+            }
+            DecodeError::CrcMismatch { expected, actual } => write!(f, "file CRC is {actual:#06x}, expected {expected:#06x}"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// The file header as found on disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub header_size: HeaderSize,
+    pub protocol_version: u8,
+    pub profile_version: u16,
+    pub data_size: u32,
+    /// CRC of the first 12 bytes, if the header has one. Zero means the
+    /// writer did not compute it.
+    pub crc: Option<u16>,
+}
+
+impl Header {
+    pub(crate) fn parse(data: &[u8]) -> Result<Header, DecodeError> {
+        let header_size = match data.first() {
+            Some(12) => HeaderSize::Short,
+            Some(14) => HeaderSize::Long,
+            _ => return Err(DecodeError::InvalidHeader),
+        };
+        let bytes = data.get(..header_size as usize).ok_or(DecodeError::InvalidHeader)?;
+        if &bytes[8..12] != b".FIT" {
+            return Err(DecodeError::InvalidHeader);
+        }
+
+        let crc = match header_size {
+            HeaderSize::Short => None,
+            HeaderSize::Long => Some(u16::from_le_bytes([bytes[12], bytes[13]])),
+        };
+
+        Ok(Header {
+            header_size,
+            protocol_version: bytes[1],
+            profile_version: u16::from_le_bytes([bytes[2], bytes[3]]),
+            data_size: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            crc,
+        })
+    }
+
+    // `data` must start with the header this was parsed from.
+    pub(crate) fn check_crc(&self, data: &[u8]) -> Result<(), DecodeError> {
+        if let Some(crc) = self.crc.filter(|&crc| crc != 0) {
+            let expected = crc_calc16(&data[..12]);
+            if crc != expected {
+                return Err(DecodeError::HeaderCrcMismatch { expected, actual: crc });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldDefinition {
+    pub num: u8,
+    pub size: u8,
+    pub base_type: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeveloperFieldDefinition {
+    pub field_definition_number: u8,
+    pub size: u8,
+    pub developer_data_index: u8,
+}
+
+/// A definition message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Definition {
+    pub local_mesg_number: u8,
+    pub big_endian: bool,
+    pub mesg_num: u16,
+    pub fields: Vec<FieldDefinition>,
+    pub developer_fields: Vec<DeveloperFieldDefinition>,
+}
+
+impl Definition {
+    fn data_size(&self) -> usize {
+        let native: usize = self.fields.iter().map(|field| field.size as usize).sum();
+        native + self.developer_fields.iter().map(|field| field.size as usize).sum::<usize>()
+    }
+}
+
+/// The value of one native field, converted to little endian.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldData {
+    pub num: u8,
+    pub base_type: u8,
+    pub value: Vec<u8>,
+}
+
+/// A data message, with its values converted to little endian.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DataMesg {
+    pub local_mesg_number: u8,
+    pub mesg_num: u16,
+    /// Timestamp carried by a compressed timestamp header, if it had one.
+    pub compressed_timestamp: Option<u32>,
+    pub fields: Vec<FieldData>,
+    pub developer_fields: Vec<DeveloperField>,
+}
+
+impl DataMesg {
+    /// The typed message, if this is a message of type `M`.
+    pub fn decode<M: Mesg>(&self) -> Option<M> {
+        let timestamp = self.compressed_timestamp.map(u32::to_le_bytes);
+        (self.mesg_num == M::MESG_NUM).then(|| mesg_from_fields(self.field_values(&timestamp)))
+    }
+
+    /// The typed message, if its type is in the bundled profile.
+    pub fn to_message(&self) -> Option<Message> {
+        let timestamp = self.compressed_timestamp.map(u32::to_le_bytes);
+        Message::from_fields(self.mesg_num, self.field_values(&timestamp))
+    }
+
+    // The native fields, plus the timestamp of a compressed header as the
+    // timestamp field.
+    fn field_values<'b>(&'b self, timestamp: &'b Option<[u8; 4]>) -> impl Iterator<Item = (u8, &'b [u8])> {
+        self.fields
+            .iter()
+            .map(|field| (field.num, &field.value[..]))
+            .chain(timestamp.as_ref().map(|bytes| (TIMESTAMP_FIELD_NUM, &bytes[..])))
+    }
+}
+
+/// One message of the record area.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Record {
+    Definition(Definition),
+    Data(DataMesg),
+}
+
+/// Reads the messages of a FIT file one at a time.
+///
+/// Definitions, normal and compressed timestamp headers and developer
+/// fields are all handled; big-endian messages come out little endian. The
+/// file CRC is checked once the record area has been read, see
+/// [`Decoder::check_crc`].
+pub struct Decoder<'a> {
+    data: &'a [u8],
+    header: Header,
+    offset: usize,
+    end: usize,
+    definitions: [Option<Definition>; LOCAL_MESG_COUNT],
+    last_timestamp: Option<u32>,
+    developer_base_types: HashMap<(u8, u8), u8>,
+}
+
+impl<'a> Decoder<'a> {
+    /// Parses and checks the file header.
+    pub fn new(data: &'a [u8]) -> Result<Self, DecodeError> {
+        let header = Header::parse(data)?;
+        header.check_crc(data)?;
+        Ok(Decoder::with_header(data, header))
+    }
+
+    // Skips the header CRC check, for tools that report it themselves.
+    pub(crate) fn with_header(data: &'a [u8], header: Header) -> Self {
+        let offset = header.header_size as usize;
+        Decoder {
+            data,
+            header,
+            offset,
+            end: offset.saturating_add(header.data_size as usize),
+            definitions: Default::default(),
+            last_timestamp: None,
+            developer_base_types: HashMap::new(),
+        }
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Byte offset of the next message.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Reads the next message, or None at the end of the record area.
+    pub fn next_record(&mut self) -> Result<Option<Record>, DecodeError> {
+        if self.offset >= self.end {
+            return Ok(None);
+        }
+        let start = self.offset;
+        let header = self.take(1)?[0];
+
+        let record = if header & 0x80 != 0 {
+            let local_mesg_number = (header >> 5) & 0x03;
+            let timestamp = self.compressed_timestamp(header & 0x1F, start)?;
+            self.read_data(local_mesg_number, Some(timestamp), start)?
+        } else if header & 0x40 != 0 {
+            self.read_definition(header & 0x0F, header & 0x20 != 0, start)?
+        } else {
+            self.read_data(header & 0x0F, None, start)?
+        };
+        Ok(Some(record))
+    }
+
+    /// Checks the CRC that follows the record area.
+    pub fn check_crc(&self) -> Result<(), DecodeError> {
+        let bytes = self.data.get(self.end..self.end + FILE_CRC_SIZE).ok_or(DecodeError::UnexpectedEof { offset: self.data.len() })?;
+        let actual = u16::from_le_bytes([bytes[0], bytes[1]]);
+        let expected = crc_calc16(&self.data[..self.end]);
+        if actual != expected {
+            return Err(DecodeError::CrcMismatch { expected, actual });
+        }
+        Ok(())
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + len)
+            .filter(|_| self.offset + len <= self.end)
+            .ok_or(DecodeError::UnexpectedEof { offset: self.offset.min(self.data.len()) })?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    // A 5-bit offset from the previous timestamp, which may have rolled over.
+    // Timestamps wrap at 32 bits like the format's own arithmetic, so input
+    // near u32::MAX cannot overflow.
+    fn compressed_timestamp(&mut self, time_offset: u8, start: usize) -> Result<u32, DecodeError> {
+        let last_timestamp = self.last_timestamp.ok_or(DecodeError::MissingTimestamp { offset: start })?;
+        let time_offset = time_offset as u32;
+        let mut timestamp = (last_timestamp & !0x1F) | time_offset;
+        if time_offset < last_timestamp & 0x1F {
+            timestamp = timestamp.wrapping_add(0x20);
+        }
+        self.last_timestamp = Some(timestamp);
+        Ok(timestamp)
+    }
+
+    fn read_definition(&mut self, local_mesg_number: u8, has_developer_fields: bool, start: usize) -> Result<Record, DecodeError> {
+        let fixed = self.take(5)?;
+        let big_endian = match fixed[1] {
+            0 => false,
+            1 => true,
+            _ => return Err(DecodeError::MalformedDefinition { offset: start }),
+        };
+        let mesg_num = if big_endian { u16::from_be_bytes([fixed[2], fixed[3]]) } else { u16::from_le_bytes([fixed[2], fixed[3]]) };
+
+        let fields = self
+            .take(fixed[4] as usize * 3)?
+            .chunks_exact(3)
+            .map(|field| FieldDefinition { num: field[0], size: field[1], base_type: field[2] })
+            .collect();
+
+        let mut developer_fields = Vec::new();
+        if has_developer_fields {
+            let count = self.take(1)?[0] as usize;
+            developer_fields = self
+                .take(count * 3)?
+                .chunks_exact(3)
+                .map(|field| DeveloperFieldDefinition { field_definition_number: field[0], size: field[1], developer_data_index: field[2] })
+                .collect();
+        }
+
+        let definition = Definition { local_mesg_number, big_endian, mesg_num, fields, developer_fields };
+        self.definitions[local_mesg_number as usize] = Some(definition.clone());
+        Ok(Record::Definition(definition))
+    }
+
+    fn read_data(&mut self, local_mesg_number: u8, compressed_timestamp: Option<u32>, start: usize) -> Result<Record, DecodeError> {
+        let definition = self.definitions[local_mesg_number as usize]
+            .take()
+            .ok_or(DecodeError::UndefinedLocalMesg { offset: start, local_mesg_number })?;
+        let result = self.read_data_with(&definition, compressed_timestamp);
+        self.definitions[local_mesg_number as usize] = Some(definition);
+        result
+    }
+
+    fn read_data_with(&mut self, definition: &Definition, compressed_timestamp: Option<u32>) -> Result<Record, DecodeError> {
+        let mut bytes = self.take(definition.data_size())?;
+
+        let mut fields = Vec::with_capacity(definition.fields.len());
+        for field in &definition.fields {
+            let (value, rest) = bytes.split_at(field.size as usize);
+            bytes = rest;
+            let mut value = value.to_vec();
+            if definition.big_endian {
+                swap_byte_order(&mut value, field.base_type);
+            }
+            if field.num == TIMESTAMP_FIELD_NUM && field.base_type == BASE_TYPE_UINT32 && value.len() == 4 {
+                self.last_timestamp = Some(u32::from_le_bytes([value[0], value[1], value[2], value[3]]));
+            }
+            fields.push(FieldData { num: field.num, base_type: field.base_type, value });
+        }
+
+        let mut developer_fields = Vec::with_capacity(definition.developer_fields.len());
+        for field in &definition.developer_fields {
+            let (value, rest) = bytes.split_at(field.size as usize);
+            bytes = rest;
+            let mut value = value.to_vec();
+            if definition.big_endian {
+                let key = (field.developer_data_index, field.field_definition_number);
+                swap_byte_order(&mut value, self.developer_base_types.get(&key).copied().unwrap_or_default());
+            }
+            developer_fields.push(DeveloperField {
+                developer_data_index: field.developer_data_index,
+                field_definition_number: field.field_definition_number,
+                value,
+            });
+        }
+
+        let mesg = DataMesg {
+            local_mesg_number: definition.local_mesg_number,
+            mesg_num: definition.mesg_num,
+            compressed_timestamp,
+            fields,
+            developer_fields,
+        };
+
+        // Later developer fields are byte swapped by their declared type.
+        if let Some(description) = mesg.decode::<FieldDescriptionMesg>() {
+            self.developer_base_types.insert(
+                (description.developer_data_index, description.field_definition_number),
+                description.fit_base_type_id,
+            );
+        }
+        Ok(Record::Data(mesg))
+    }
+}
+
+impl Iterator for Decoder<'_> {
+    type Item = Result<Record, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.next_record().transpose();
+        if matches!(record, Some(Err(_))) {
+            // Nothing after an error can be trusted.
+            self.offset = self.end;
+        }
+        record
+    }
+}
+
+/// A whole decoded FIT file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FitFile {
+    pub header: Header,
+    pub records: Vec<Record>,
+}
+
+impl FitFile {
+    /// The data messages, in file order.
+    pub fn data_mesgs(&self) -> impl Iterator<Item = &DataMesg> {
+        self.records.iter().filter_map(|record| match record {
+            Record::Data(mesg) => Some(mesg),
+            Record::Definition(_) => None,
+        })
+    }
+
+    /// The typed data messages of the bundled profile, in file order.
+    pub fn messages(&self) -> impl Iterator<Item = Message> + '_ {
+        self.data_mesgs().filter_map(DataMesg::to_message)
+    }
+}
+
+/// Decodes a complete FIT file, checking both CRCs.
+pub fn decode(data: &[u8]) -> Result<FitFile, DecodeError> {
+    let mut decoder = Decoder::new(data)?;
+    let mut records = Vec::new();
+    while let Some(record) = decoder.next_record()? {
+        records.push(record);
+    }
+    decoder.check_crc()?;
+    Ok(FitFile { header: decoder.header, records })
+}
@@ -0,0 +1,444 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Write;
+
+use chrono::DateTime;
+
+use crate::decoder::{DataMesg, DecodeError, Decoder, Definition, Header, Record};
+use crate::error::FitError;
+use crate::profile::{enum_value_name, mesg_profile, FieldDescriptionMesg};
+use crate::schema::*;
+
+// Seconds from the Unix epoch to the FIT epoch, 1989-12-31T00:00:00Z.
+const FIT_EPOCH_OFFSET: i64 = 631_065_600;
+// date_time values below this count seconds since device power-on.
+const DATE_TIME_MIN: i128 = 0x1000_0000;
+
+/// How [`dump`] renders a file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// One line per message and per field, for reading.
+    #[default]
+    Text,
+    /// A single JSON document, for scripts.
+    Json,
+}
+
+/// Writes every definition and data message of a FIT file to `out`.
+///
+/// Fields of messages in the bundled profile are shown with their names,
+/// units, scaled values and enum names; developer fields are named after
+/// their field description. Messages and fields the profile does not know
+/// are shown by number with their raw values. Decoding stops at the first
+/// error, which is included in the output and returned.
+pub fn dump<W: Write>(data: &[u8], format: DumpFormat, out: &mut W) -> Result<(), FitError> {
+    let mut decoder = Decoder::new(data)?;
+    let mut printer = Printer { out, format, first_record: true };
+    printer.header(decoder.header())?;
+
+    let mut developer_fields = HashMap::new();
+    let result = loop {
+        let offset = decoder.offset();
+        match decoder.next_record() {
+            Ok(Some(Record::Definition(definition))) => printer.definition(offset, &definition)?,
+            Ok(Some(Record::Data(mesg))) => {
+                if let Some(description) = mesg.decode::<FieldDescriptionMesg>() {
+                    developer_fields.insert((description.developer_data_index, description.field_definition_number), description);
+                }
+                printer.data(offset, &mesg, &developer_fields)?;
+            }
+            Ok(None) => break decoder.check_crc(),
+            Err(error) => break Err(error),
+        }
+    };
+
+    printer.footer(result.as_ref().err())?;
+    result.map_err(FitError::from)
+}
+
+// A field value ready for display.
+enum Value {
+    Invalid,
+    Int(i128),
+    Float(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+    Enum(&'static str, i128),
+    DateTime(i128),
+    Array(Vec<Value>),
+}
+
+// What the profile, or a field description, says about a field.
+struct FieldInfo<'a> {
+    name: Cow<'a, str>,
+    profile_type: &'a str,
+    scale: f64,
+    offset: f64,
+    units: &'a str,
+}
+
+impl<'a> FieldInfo<'a> {
+    fn native(mesg_num: u16, num: u8) -> FieldInfo<'static> {
+        let field = mesg_profile(mesg_num).and_then(|(_, fields)| fields.iter().find(|field| field.num == num));
+        match field {
+            Some(field) => FieldInfo {
+                name: Cow::Borrowed(field.name),
+                profile_type: field.profile_type,
+                scale: field.scale,
+                offset: field.offset,
+                units: field.units,
+            },
+            None => FieldInfo::unknown(format!("field_{num}")),
+        }
+    }
+
+    fn developer(description: Option<&'a FieldDescriptionMesg>, developer_data_index: u8, num: u8) -> Self {
+        let Some(description) = description else {
+            return FieldInfo::unknown(format!("developer_{developer_data_index}_{num}"));
+        };
+        let name = match description.field_name.as_str() {
+            "" => Cow::Owned(format!("developer_{developer_data_index}_{num}")),
+            name => Cow::Borrowed(name),
+        };
+        FieldInfo {
+            name,
+            profile_type: "",
+            scale: if description.scale.is_invalid(BASE_TYPE_UINT8) { 1.0 } else { description.scale as f64 },
+            offset: if description.offset.is_invalid(BASE_TYPE_SINT8) { 0.0 } else { description.offset as f64 },
+            units: description.units.as_str(),
+        }
+    }
+
+    fn unknown(name: String) -> Self {
+        FieldInfo { name: Cow::Owned(name), profile_type: "", scale: 1.0, offset: 0.0, units: "" }
+    }
+
+    fn value(&self, value: &[u8], base_type: u8) -> Value {
+        match base_type {
+            BASE_TYPE_STRING => {
+                let end = value.iter().position(|&byte| byte == 0).unwrap_or(value.len());
+                match end {
+                    0 => Value::Invalid,
+                    _ => Value::Text(String::from_utf8_lossy(&value[..end]).into_owned()),
+                }
+            }
+            BASE_TYPE_BYTE if value.iter().all(|&byte| byte == FIT_BYTE_INVALID) => Value::Invalid,
+            BASE_TYPE_BYTE => Value::Bytes(value.to_vec()),
+            _ => {
+                let size = base_type_size(base_type) as usize;
+                let mut elements: Vec<Value> = value.chunks_exact(size).map(|element| self.element(element, base_type)).collect();
+                if elements.len() == 1 {
+                    elements.pop().unwrap()
+                } else {
+                    Value::Array(elements)
+                }
+            }
+        }
+    }
+
+    // Same as raw / scale - offset, but exact for the usual integer
+    // offset * scale, so e.g. altitude reads -3.2 m rather than
+    // -3.1999999999999886 m.
+    fn scale_raw(&self, raw: f64) -> f64 {
+        (raw - self.offset * self.scale) / self.scale
+    }
+
+    fn element(&self, bytes: &[u8], base_type: u8) -> Value {
+        let raw = match base_type {
+            BASE_TYPE_SINT8 => int::<i8>(bytes, base_type),
+            BASE_TYPE_SINT16 => int::<i16>(bytes, base_type),
+            BASE_TYPE_UINT16 | BASE_TYPE_UINT16Z => int::<u16>(bytes, base_type),
+            BASE_TYPE_SINT32 => int::<i32>(bytes, base_type),
+            BASE_TYPE_UINT32 | BASE_TYPE_UINT32Z => int::<u32>(bytes, base_type),
+            BASE_TYPE_SINT64 => int::<i64>(bytes, base_type),
+            BASE_TYPE_UINT64 | BASE_TYPE_UINT64Z => int::<u64>(bytes, base_type),
+            BASE_TYPE_FLOAT32 => float::<f32>(bytes, base_type),
+            BASE_TYPE_FLOAT64 => float::<f64>(bytes, base_type),
+            _ => int::<u8>(bytes, base_type),
+        };
+        let scaled = self.scale != 1.0 || self.offset != 0.0;
+        match raw {
+            Value::Int(raw) => match u32::try_from(raw).ok().and_then(|value| enum_value_name(self.profile_type, value)) {
+                Some(name) => Value::Enum(name, raw),
+                None if self.profile_type == "date_time" && raw >= DATE_TIME_MIN => Value::DateTime(raw),
+                None if scaled => Value::Float(self.scale_raw(raw as f64)),
+                None => Value::Int(raw),
+            },
+            Value::Float(raw) if scaled => Value::Float(self.scale_raw(raw)),
+            raw => raw,
+        }
+    }
+}
+
+fn int<T: FieldValue + Into<i128>>(bytes: &[u8], base_type: u8) -> Value {
+    match T::read_from(bytes, base_type) {
+        Some(value) if !value.is_invalid(base_type) => Value::Int(value.into()),
+        _ => Value::Invalid,
+    }
+}
+
+fn float<T: FieldValue + Into<f64>>(bytes: &[u8], base_type: u8) -> Value {
+    match T::read_from(bytes, base_type) {
+        Some(value) if !value.is_invalid(base_type) => Value::Float(value.into()),
+        _ => Value::Invalid,
+    }
+}
+
+fn mesg_name(mesg_num: u16) -> Cow<'static, str> {
+    match mesg_profile(mesg_num) {
+        Some((name, _)) => Cow::Borrowed(name),
+        None => Cow::Owned(format!("mesg_{mesg_num}")),
+    }
+}
+
+fn date_time(value: i128) -> String {
+    let seconds = i64::try_from(value).unwrap_or(i64::MAX).saturating_add(FIT_EPOCH_OFFSET);
+    match DateTime::from_timestamp(seconds, 0) {
+        Some(time) => time.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        None => value.to_string(),
+    }
+}
+
+struct Printer<'a, W: Write> {
+    out: &'a mut W,
+    format: DumpFormat,
+    first_record: bool,
+}
+
+impl<W: Write> Printer<'_, W> {
+    fn header(&mut self, header: &Header) -> Result<(), FitError> {
+        let protocol = format!("{}.{}", header.protocol_version >> 4, header.protocol_version & 0x0F);
+        let profile = format!("{}.{}", header.profile_version / 1000, header.profile_version % 1000);
+        match self.format {
+            DumpFormat::Text => {
+                write!(self.out, "header: {} bytes, protocol {protocol}, profile {profile}, {} data bytes", header.header_size as u8, header.data_size)?;
+                match header.crc {
+                    Some(crc) => writeln!(self.out, ", CRC {crc:#06x}")?,
+                    None => writeln!(self.out)?,
+                }
+            }
+            DumpFormat::Json => {
+                write!(
+                    self.out,
+                    "{{\"header\":{{\"header_size\":{},\"protocol_version\":\"{protocol}\",\"profile_version\":\"{profile}\",\"data_size\":{},\"crc\":",
+                    header.header_size as u8, header.data_size
+                )?;
+                match header.crc {
+                    Some(crc) => write!(self.out, "{crc}")?,
+                    None => write!(self.out, "null")?,
+                }
+                write!(self.out, "}},\n\"records\":[")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn definition(&mut self, offset: usize, definition: &Definition) -> Result<(), FitError> {
+        let mesg_num = definition.mesg_num;
+        let name = mesg_name(mesg_num);
+        let architecture = if definition.big_endian { "big_endian" } else { "little_endian" };
+        match self.format {
+            DumpFormat::Text => {
+                let architecture = architecture.replace('_', " ");
+                writeln!(self.out, "@{offset} definition {}: {name} ({mesg_num}), {architecture}", definition.local_mesg_number)?;
+                for field in &definition.fields {
+                    let info = FieldInfo::native(mesg_num, field.num);
+                    writeln!(self.out, "    {} {}: {}, {} bytes", field.num, info.name, base_type_name(field.base_type), field.size)?;
+                }
+                for field in &definition.developer_fields {
+                    let (index, num) = (field.developer_data_index, field.field_definition_number);
+                    writeln!(self.out, "    developer {index}.{num}: {} bytes", field.size)?;
+                }
+            }
+            DumpFormat::Json => {
+                self.record_separator()?;
+                write!(
+                    self.out,
+                    "{{\"offset\":{offset},\"kind\":\"definition\",\"local_mesg_number\":{},\"mesg_num\":{mesg_num},\"name\":",
+                    definition.local_mesg_number
+                )?;
+                write_json_str(self.out, &name)?;
+                write!(self.out, ",\"architecture\":\"{architecture}\",\"fields\":[")?;
+                for (i, field) in definition.fields.iter().enumerate() {
+                    let info = FieldInfo::native(mesg_num, field.num);
+                    write!(self.out, "{}{{\"num\":{},\"name\":", if i > 0 { "," } else { "" }, field.num)?;
+                    write_json_str(self.out, &info.name)?;
+                    write!(self.out, ",\"base_type\":\"{}\",\"size\":{}}}", base_type_name(field.base_type), field.size)?;
+                }
+                write!(self.out, "],\"developer_fields\":[")?;
+                for (i, field) in definition.developer_fields.iter().enumerate() {
+                    write!(
+                        self.out,
+                        "{}{{\"developer_data_index\":{},\"num\":{},\"size\":{}}}",
+                        if i > 0 { "," } else { "" },
+                        field.developer_data_index,
+                        field.field_definition_number,
+                        field.size
+                    )?;
+                }
+                write!(self.out, "]}}")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn data(&mut self, offset: usize, mesg: &DataMesg, descriptions: &HashMap<(u8, u8), FieldDescriptionMesg>) -> Result<(), FitError> {
+        let mesg_num = mesg.mesg_num;
+        let name = mesg_name(mesg_num);
+        let timestamp = mesg.compressed_timestamp.map(u32::to_le_bytes);
+
+        let mut fields: Vec<(FieldInfo, Value)> = Vec::new();
+        for field in &mesg.fields {
+            let info = FieldInfo::native(mesg_num, field.num);
+            let value = info.value(&field.value, field.base_type);
+            fields.push((info, value));
+        }
+        if let Some(timestamp) = &timestamp {
+            let info = FieldInfo::native(mesg_num, 253);
+            let value = info.value(timestamp, BASE_TYPE_UINT32);
+            fields.push((info, value));
+        }
+        for field in &mesg.developer_fields {
+            let (index, num) = (field.developer_data_index, field.field_definition_number);
+            let description = descriptions.get(&(index, num));
+            let info = FieldInfo::developer(description, index, num);
+            // Without a description the value can only be shown as bytes.
+            let base_type = description.map_or(BASE_TYPE_BYTE, |description| description.fit_base_type_id);
+            let value = info.value(&field.value, base_type);
+            fields.push((info, value));
+        }
+
+        match self.format {
+            DumpFormat::Text => {
+                let compressed = if mesg.compressed_timestamp.is_some() { ", compressed timestamp" } else { "" };
+                writeln!(self.out, "@{offset} data {}: {name} ({mesg_num}){compressed}", mesg.local_mesg_number)?;
+                for (info, value) in &fields {
+                    write!(self.out, "    {}: ", info.name)?;
+                    write_text_value(self.out, value)?;
+                    if !info.units.is_empty() && !matches!(value, Value::Invalid | Value::Enum(..) | Value::DateTime(_)) {
+                        write!(self.out, " {}", info.units)?;
+                    }
+                    writeln!(self.out)?;
+                }
+            }
+            DumpFormat::Json => {
+                self.record_separator()?;
+                write!(
+                    self.out,
+                    "{{\"offset\":{offset},\"kind\":\"data\",\"local_mesg_number\":{},\"mesg_num\":{mesg_num},\"name\":",
+                    mesg.local_mesg_number
+                )?;
+                write_json_str(self.out, &name)?;
+                write!(self.out, ",\"fields\":{{")?;
+                for (i, (info, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.out, ",")?;
+                    }
+                    write_json_str(self.out, &info.name)?;
+                    write!(self.out, ":{{\"value\":")?;
+                    write_json_value(self.out, value)?;
+                    if !info.units.is_empty() {
+                        write!(self.out, ",\"units\":")?;
+                        write_json_str(self.out, info.units)?;
+                    }
+                    write!(self.out, "}}")?;
+                }
+                write!(self.out, "}}}}")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn footer(&mut self, error: Option<&DecodeError>) -> Result<(), FitError> {
+        match (self.format, error) {
+            (DumpFormat::Text, None) => writeln!(self.out, "file CRC ok")?,
+            (DumpFormat::Text, Some(error)) => writeln!(self.out, "error: {error}")?,
+            (DumpFormat::Json, None) => writeln!(self.out, "\n],\"error\":null}}")?,
+            (DumpFormat::Json, Some(error)) => {
+                write!(self.out, "\n],\"error\":")?;
+                write_json_str(self.out, &error.to_string())?;
+                writeln!(self.out, "}}")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn record_separator(&mut self) -> Result<(), FitError> {
+        let separator = if self.first_record { "\n" } else { ",\n" };
+        self.first_record = false;
+        self.out.write_all(separator.as_bytes())?;
+        Ok(())
+    }
+}
+
+fn write_text_value<W: Write>(out: &mut W, value: &Value) -> Result<(), FitError> {
+    match value {
+        Value::Invalid => write!(out, "invalid")?,
+        Value::Int(value) => write!(out, "{value}")?,
+        Value::Float(value) => write!(out, "{value}")?,
+        Value::Text(value) => write!(out, "{value:?}")?,
+        Value::Bytes(bytes) => {
+            for byte in bytes {
+                write!(out, "{byte:02x}")?;
+            }
+        }
+        Value::Enum(name, raw) => write!(out, "{name} ({raw})")?,
+        Value::DateTime(raw) => write!(out, "{} ({raw})", date_time(*raw))?,
+        Value::Array(values) => {
+            write!(out, "[")?;
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    write!(out, ", ")?;
+                }
+                write_text_value(out, value)?;
+            }
+            write!(out, "]")?;
+        }
+    }
+    Ok(())
+}
+
+// Enums come out as their names and date_time values as their raw
+// number, which is what other FIT tools compare against.
+fn write_json_value<W: Write>(out: &mut W, value: &Value) -> Result<(), FitError> {
+    match value {
+        Value::Invalid => write!(out, "null")?,
+        Value::Int(value) | Value::DateTime(value) => write!(out, "{value}")?,
+        Value::Float(value) if value.is_finite() => write!(out, "{value}")?,
+        Value::Float(_) => write!(out, "null")?,
+        Value::Text(value) => write_json_str(out, value)?,
+        Value::Enum(name, _) => write_json_str(out, name)?,
+        Value::Bytes(bytes) => {
+            let values: Vec<Value> = bytes.iter().map(|&byte| Value::Int(byte.into())).collect();
+            write_json_value(out, &Value::Array(values))?;
+        }
+        Value::Array(values) => {
+            write!(out, "[")?;
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    write!(out, ",")?;
+                }
+                write_json_value(out, value)?;
+            }
+            write!(out, "]")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_json_str<W: Write>(out: &mut W, s: &str) -> Result<(), FitError> {
+    write!(out, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(out, "\\\"")?,
+            '\\' => write!(out, "\\\\")?,
+            '\n' => write!(out, "\\n")?,
+            '\r' => write!(out, "\\r")?,
+            '\t' => write!(out, "\\t")?,
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32)?,
+            c => write!(out, "{c}")?,
+        }
+    }
+    write!(out, "\"")?;
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use std::io::Write;
+
+use crate::messages::{write_developer_data_id, write_field_description, DeveloperField};
+use crate::profile::{DeveloperDataIdMesg, FieldDescriptionMesg};
+use crate::schema::{to_raw, FieldRangeError, BASE_TYPE_UINT16, BASE_TYPE_UINT8};
+use crate::error::FitError;
+use crate::writer::FitWriter;
+
+pub const ECHO_DEVELOPER_DATA_INDEX: u8 = 0;
+
+// Identifies echo2garmin as the application that declared the fields below.
+const ECHO_APPLICATION_ID: [u8; 16] = [
+    0x6e, 0x3b, 0x1c, 0x52, 0x9a, 0x4f, 0x4d, 0x21, 0xb0, 0x77, 0x0e, 0x5d, 0xc4, 0x18, 0xe2, 0x93,
+];
+const ECHO_APPLICATION_VERSION: u32 = 1;
+
+/// Metrics reported by the Echo console that have no native FIT field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EchoMetric {
+    ConsoleCalories(u16), // kcal, as counted by the console
+    CaloriesPerMinute(f64), // kcal/min
+    ProgramNumber(u8), // Interval/program selected on the console
+    FanRpm(u16), // Raw fan speed
+}
+
+struct EchoFieldDescription {
+    field_definition_number: u8,
+    name: &'static str,
+    units: &'static str,
+    base_type: u8,
+    scale: u8,
+}
+
+const CONSOLE_CALORIES: EchoFieldDescription =
+    EchoFieldDescription { field_definition_number: 0, name: "console_calories", units: "kcal", base_type: BASE_TYPE_UINT16, scale: 1 };
+const CALORIES_PER_MINUTE: EchoFieldDescription =
+    EchoFieldDescription { field_definition_number: 1, name: "calories_per_minute", units: "kcal/min", base_type: BASE_TYPE_UINT16, scale: 10 };
+const PROGRAM_NUMBER: EchoFieldDescription =
+    EchoFieldDescription { field_definition_number: 2, name: "program_number", units: "", base_type: BASE_TYPE_UINT8, scale: 1 };
+const FAN_RPM: EchoFieldDescription =
+    EchoFieldDescription { field_definition_number: 3, name: "fan_rpm", units: "rpm", base_type: BASE_TYPE_UINT16, scale: 1 };
+
+const ECHO_FIELDS: [&EchoFieldDescription; 4] = [&CONSOLE_CALORIES, &CALORIES_PER_MINUTE, &PROGRAM_NUMBER, &FAN_RPM];
+
+impl EchoMetric {
+    // The field the metric is written to, and its value in that field's units.
+    fn field(self) -> (&'static EchoFieldDescription, f64) {
+        match self {
+            EchoMetric::ConsoleCalories(kcal) => (&CONSOLE_CALORIES, kcal as f64),
+            EchoMetric::CaloriesPerMinute(kcal_per_min) => (&CALORIES_PER_MINUTE, kcal_per_min),
+            EchoMetric::ProgramNumber(program) => (&PROGRAM_NUMBER, program as f64),
+            EchoMetric::FanRpm(rpm) => (&FAN_RPM, rpm as f64),
+        }
+    }
+}
+
+/// Encodes a metric as declared by [`write_echo_developer_data`]. Fails for
+/// values the field cannot hold, including NaN and values that would land on
+/// the field's invalid value, such as `ConsoleCalories(0xFFFF)`.
+impl TryFrom<EchoMetric> for DeveloperField {
+    type Error = FieldRangeError;
+
+    fn try_from(metric: EchoMetric) -> Result<Self, FieldRangeError> {
+        let (field, value) = metric.field();
+        let (name, base_type, scale) = (field.name, field.base_type, field.scale as f64);
+        let value = match base_type {
+            BASE_TYPE_UINT8 => to_raw::<u8>(name, value, base_type, scale, 0.0)?.to_le_bytes().to_vec(),
+            BASE_TYPE_UINT16 => to_raw::<u16>(name, value, base_type, scale, 0.0)?.to_le_bytes().to_vec(),
+            _ => unreachable!("Echo field {name} has an unsupported base type"),
+        };
+        Ok(DeveloperField { developer_data_index: ECHO_DEVELOPER_DATA_INDEX, field_definition_number: field.field_definition_number, value })
+    }
+}
+
+/// Declares the Echo developer fields. Has to be written before any message
+/// that carries an [`EchoMetric`].
+pub fn write_echo_developer_data<W: Write>(fit: &mut FitWriter<W>) -> Result<(), FitError> {
+    write_developer_data_id(fit, &DeveloperDataIdMesg {
+        application_id: ECHO_APPLICATION_ID,
+        application_version: ECHO_APPLICATION_VERSION,
+        developer_data_index: ECHO_DEVELOPER_DATA_INDEX,
+        ..Default::default()
+    })?;
+
+    for field in ECHO_FIELDS {
+        write_field_description(fit, &FieldDescriptionMesg {
+            developer_data_index: ECHO_DEVELOPER_DATA_INDEX,
+            field_definition_number: field.field_definition_number,
+            fit_base_type_id: field.base_type,
+            field_name: field.name.into(),
+            units: field.units.into(),
+            scale: field.scale,
+            offset: 0,
+            ..Default::default()
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::decoder::DecodeError;
+use crate::schema::FieldRangeError;
+
+/// Everything that can go wrong while encoding or decoding a FIT file.
+#[derive(Debug)]
+pub enum FitError {
+    /// The underlying writer failed, e.g. because the disk is full.
+    Io(io::Error),
+    /// A physical value does not fit its field.
+    FieldRange(FieldRangeError),
+    /// A message has more fields, or a field more bytes, than a definition
+    /// can describe.
+    DefinitionOverflow { mesg_num: u16 },
+    /// The writer was asked to do something its current state does not
+    /// allow, such as using a developer field that was never described.
+    InvalidState(String),
+    /// A file being read is not valid FIT.
+    Decode(DecodeError),
+}
+
+impl fmt::Display for FitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FitError::Io(error) => write!(f, "I/O error: {error}"),
+            FitError::FieldRange(error) => error.fmt(f),
+            FitError::DefinitionOverflow { mesg_num } => write!(f, "message {mesg_num} does not fit in a definition"),
+            FitError::InvalidState(reason) => f.write_str(reason),
+            FitError::Decode(error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for FitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FitError::Io(error) => Some(error),
+            FitError::FieldRange(error) => Some(error),
+            FitError::Decode(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FitError {
+    fn from(error: io::Error) -> Self {
+        FitError::Io(error)
+    }
+}
+
+impl From<FieldRangeError> for FitError {
+    fn from(error: FieldRangeError) -> Self {
+        FitError::FieldRange(error)
+    }
+}
+
+impl From<DecodeError> for FitError {
+    fn from(error: DecodeError) -> Self {
+        FitError::Decode(error)
+    }
+}
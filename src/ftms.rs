@@ -0,0 +1,254 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::profile::RecordMesg;
+
+/// UUID of the Fitness Machine Service Indoor Bike Data characteristic.
+pub const INDOOR_BIKE_DATA_UUID: u16 = 0x2AD2;
+
+const FLAGS_SIZE: usize = 2;
+
+// Indoor Bike Data flag bits. Bit 0 is inverted: the instantaneous speed is
+// present when it is clear.
+const MORE_DATA: u16 = 1 << 0;
+const AVERAGE_SPEED: u16 = 1 << 1;
+const INSTANTANEOUS_CADENCE: u16 = 1 << 2;
+const AVERAGE_CADENCE: u16 = 1 << 3;
+const TOTAL_DISTANCE: u16 = 1 << 4;
+const RESISTANCE_LEVEL: u16 = 1 << 5;
+const INSTANTANEOUS_POWER: u16 = 1 << 6;
+const AVERAGE_POWER: u16 = 1 << 7;
+const EXPENDED_ENERGY: u16 = 1 << 8;
+const HEART_RATE: u16 = 1 << 9;
+const METABOLIC_EQUIVALENT: u16 = 1 << 10;
+const ELAPSED_TIME: u16 = 1 << 11;
+const REMAINING_TIME: u16 = 1 << 12;
+
+// Optional fields in the order they follow the flags, with their sizes.
+const FIELDS: [(u16, usize); 12] = [
+    (AVERAGE_SPEED, 2),
+    (INSTANTANEOUS_CADENCE, 2),
+    (AVERAGE_CADENCE, 2),
+    (TOTAL_DISTANCE, 3),
+    (RESISTANCE_LEVEL, 2),
+    (INSTANTANEOUS_POWER, 2),
+    (AVERAGE_POWER, 2),
+    (EXPENDED_ENERGY, 5),
+    (HEART_RATE, 1),
+    (METABOLIC_EQUIVALENT, 1),
+    (ELAPSED_TIME, 2),
+    (REMAINING_TIME, 2),
+];
+
+// Expended energy values the machine sends when it does not know them.
+const ENERGY_NOT_AVAILABLE: u16 = 0xFFFF;
+const ENERGY_PER_MINUTE_NOT_AVAILABLE: u8 = 0xFF;
+
+/// An Indoor Bike Data notification that is shorter than its flags say.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FtmsError {
+    Truncated { len: usize, expected: usize },
+}
+
+impl fmt::Display for FtmsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FtmsError::Truncated { len, expected } => {
+                write!(f, "indoor bike data is {len} bytes, but its flags call for {expected}")
+            }
+        }
+    }
+}
+
+impl Error for FtmsError {}
+
+/// The fields of one Indoor Bike Data notification, in physical units.
+/// Fields the flags leave out are `None`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IndoorBikeData {
+    /// The rest of the fields follow in the next notification.
+    pub more_data: bool,
+    pub instantaneous_speed: Option<f64>, // km/h
+    pub average_speed: Option<f64>, // km/h
+    pub instantaneous_cadence: Option<f64>, // rpm
+    pub average_cadence: Option<f64>, // rpm
+    pub total_distance: Option<u32>, // m
+    pub resistance_level: Option<i16>, // Unitless, within the machine's supported range
+    pub instantaneous_power: Option<i16>, // W
+    pub average_power: Option<i16>, // W
+    pub total_energy: Option<u16>, // kcal
+    pub energy_per_hour: Option<u16>, // kcal/h
+    pub energy_per_minute: Option<u8>, // kcal/min
+    pub heart_rate: Option<u8>, // bpm
+    pub metabolic_equivalent: Option<f64>, // MET
+    pub elapsed_time: Option<u16>, // s
+    pub remaining_time: Option<u16>, // s
+}
+
+impl IndoorBikeData {
+    /// Parses the value of an Indoor Bike Data notification.
+    ///
+    /// Bytes after the last field the flags announce are ignored, as they
+    /// belong to fields added by later versions of the service.
+    pub fn parse(value: &[u8]) -> Result<IndoorBikeData, FtmsError> {
+        let flags = match value {
+            [low, high, ..] => u16::from_le_bytes([*low, *high]),
+            _ => return Err(FtmsError::Truncated { len: value.len(), expected: FLAGS_SIZE }),
+        };
+        let expected = FLAGS_SIZE
+            + if flags & MORE_DATA == 0 { 2 } else { 0 }
+            + FIELDS.iter().filter(|(flag, _)| flags & flag != 0).map(|(_, size)| size).sum::<usize>();
+        if value.len() < expected {
+            return Err(FtmsError::Truncated { len: value.len(), expected });
+        }
+
+        let mut bytes = Reader(&value[FLAGS_SIZE..]);
+        let has = |flag: u16| flags & flag != 0;
+
+        let mut data = IndoorBikeData { more_data: has(MORE_DATA), ..Default::default() };
+        if !has(MORE_DATA) {
+            data.instantaneous_speed = Some(bytes.u16() as f64 * 0.01);
+        }
+        if has(AVERAGE_SPEED) {
+            data.average_speed = Some(bytes.u16() as f64 * 0.01);
+        }
+        if has(INSTANTANEOUS_CADENCE) {
+            data.instantaneous_cadence = Some(bytes.u16() as f64 * 0.5);
+        }
+        if has(AVERAGE_CADENCE) {
+            data.average_cadence = Some(bytes.u16() as f64 * 0.5);
+        }
+        if has(TOTAL_DISTANCE) {
+            let low = bytes.u16() as u32;
+            data.total_distance = Some(low | (bytes.u8() as u32) << 16);
+        }
+        if has(RESISTANCE_LEVEL) {
+            data.resistance_level = Some(bytes.u16() as i16);
+        }
+        if has(INSTANTANEOUS_POWER) {
+            data.instantaneous_power = Some(bytes.u16() as i16);
+        }
+        if has(AVERAGE_POWER) {
+            data.average_power = Some(bytes.u16() as i16);
+        }
+        if has(EXPENDED_ENERGY) {
+            data.total_energy = Some(bytes.u16()).filter(|&kcal| kcal != ENERGY_NOT_AVAILABLE);
+            data.energy_per_hour = Some(bytes.u16()).filter(|&kcal| kcal != ENERGY_NOT_AVAILABLE);
+            data.energy_per_minute = Some(bytes.u8()).filter(|&kcal| kcal != ENERGY_PER_MINUTE_NOT_AVAILABLE);
+        }
+        if has(HEART_RATE) {
+            data.heart_rate = Some(bytes.u8());
+        }
+        if has(METABOLIC_EQUIVALENT) {
+            data.metabolic_equivalent = Some(bytes.u8() as f64 * 0.1);
+        }
+        if has(ELAPSED_TIME) {
+            data.elapsed_time = Some(bytes.u16());
+        }
+        if has(REMAINING_TIME) {
+            data.remaining_time = Some(bytes.u16());
+        }
+        Ok(data)
+    }
+
+    /// Adds the fields of a later notification that continues this one.
+    /// Fields both carry take the later value.
+    pub fn merge(&mut self, next: &IndoorBikeData) {
+        macro_rules! merge_fields {
+            ($($field:ident),*) => {
+                $(self.$field = next.$field.or(self.$field);)*
+            };
+        }
+        merge_fields!(
+            instantaneous_speed, average_speed, instantaneous_cadence, average_cadence, total_distance, resistance_level,
+            instantaneous_power, average_power, total_energy, energy_per_hour, energy_per_minute, heart_rate,
+            metabolic_equivalent, elapsed_time, remaining_time
+        );
+        self.more_data = next.more_data;
+    }
+
+    /// A record message with the instantaneous values of this sample. Fields
+    /// the machine did not send are left invalid.
+    pub fn to_record(&self, timestamp: u32) -> RecordMesg {
+        let mut record = RecordMesg { timestamp, ..Default::default() };
+        if let Some(speed) = self.instantaneous_speed {
+            record.set_speed_saturating(speed / 3.6);
+        }
+        if let Some(cadence) = self.instantaneous_cadence {
+            record.set_cadence_saturating(cadence);
+        }
+        if let Some(power) = self.instantaneous_power {
+            record.set_power_saturating(power as f64);
+        }
+        if let Some(distance) = self.total_distance {
+            record.set_distance_saturating(distance as f64);
+        }
+        if let Some(calories) = self.total_energy {
+            record.set_calories_saturating(calories as f64);
+        }
+        if let Some(heart_rate) = self.heart_rate {
+            record.set_heart_rate_saturating(heart_rate as f64);
+        }
+        record
+    }
+}
+
+// Reads little-endian values off the front of a notification whose length
+// has already been checked against its flags.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn u8(&mut self) -> u8 {
+        let (&value, rest) = self.0.split_first().unwrap();
+        self.0 = rest;
+        value
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes([self.u8(), self.u8()])
+    }
+}
+
+/// Turns a stream of Indoor Bike Data notifications into record messages.
+///
+/// Notifications with the More Data flag set are held back and merged with
+/// the ones that follow until a sample is complete. Samples are timestamped
+/// by the machine's elapsed time when it reports one, anchored to the time
+/// the first such sample arrived, and by their arrival time otherwise.
+#[derive(Clone, Debug, Default)]
+pub struct IndoorBikeSampler {
+    pending: Option<IndoorBikeData>,
+    start_time: Option<u32>,
+}
+
+impl IndoorBikeSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes a notification that arrived at `received`, a FIT timestamp, and
+    /// returns the record for the sample it completes, if any.
+    pub fn push(&mut self, value: &[u8], received: u32) -> Result<Option<RecordMesg>, FtmsError> {
+        let data = IndoorBikeData::parse(value)?;
+        let data = match self.pending.take() {
+            Some(mut pending) => {
+                pending.merge(&data);
+                pending
+            }
+            None => data,
+        };
+        if data.more_data {
+            self.pending = Some(data);
+            return Ok(None);
+        }
+
+        let timestamp = match data.elapsed_time {
+            Some(elapsed) => {
+                let start_time = *self.start_time.get_or_insert(received.wrapping_sub(elapsed as u32));
+                start_time.wrapping_add(elapsed as u32)
+            }
+            None => received,
+        };
+        Ok(Some(data.to_record(timestamp)))
+    }
+}
@@ -0,0 +1,39 @@
+use crate::crc::crc_calc16;
+
+pub(crate) const PROTOCOL_VERSION_1: u8 = 0x10; // 1.0
+pub(crate) const PROTOCOL_VERSION_2: u8 = 0x20; // 2.0, needed for developer fields
+pub(crate) const PROFILE_VERSION: u16 = 21158;
+
+/// Size of the FIT file header. The 14 byte form carries a CRC of bytes 0-11.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeaderSize {
+    Short = 12,
+    #[default]
+    Long = 14,
+}
+
+pub(crate) struct FileHeader {
+    pub header_size: HeaderSize,
+    pub protocol_version: u8,
+    pub profile_version: u16,
+    pub data_size: u32, // Length of the record area, excluding header and file CRC
+}
+
+impl FileHeader {
+    // Serialized field by field in little endian, so the bytes are the same
+    // whatever the host byte order.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::with_capacity(self.header_size as usize);
+        buffer.push(self.header_size as u8);
+        buffer.push(self.protocol_version);
+        buffer.extend_from_slice(&self.profile_version.to_le_bytes());
+        buffer.extend_from_slice(&self.data_size.to_le_bytes());
+        buffer.extend_from_slice(b".FIT");
+
+        if self.header_size == HeaderSize::Long {
+            let crc = crc_calc16(&buffer);
+            buffer.extend_from_slice(&crc.to_le_bytes());
+        }
+        buffer
+    }
+}
@@ -0,0 +1,35 @@
+mod components;
+mod crc;
+mod decoder;
+mod dump;
+mod echo;
+mod error;
+mod ftms;
+mod header;
+mod messages;
+pub mod profile;
+mod repair;
+pub mod schema;
+mod validate;
+mod writer;
+
+pub use components::{compress_speed_distance, Accumulator};
+pub use decoder::{
+    decode, DataMesg, DecodeError, Decoder, Definition, DeveloperFieldDefinition, FieldData, FieldDefinition, FitFile, Header,
+    Record,
+};
+pub use dump::{dump, DumpFormat};
+pub use echo::{write_echo_developer_data, EchoMetric, ECHO_DEVELOPER_DATA_INDEX};
+pub use error::FitError;
+pub use ftms::{FtmsError, IndoorBikeData, IndoorBikeSampler, INDOOR_BIKE_DATA_UUID};
+pub use messages::{
+    write_activity, write_activity_file, write_developer_data_id, write_device_info_message, write_field_description,
+    write_file_id_message, write_lap, write_mesg, write_record, write_session, write_start_event,
+    write_stop_event, DeveloperField,
+};
+pub use profile::{DeveloperDataIdMesg, DeviceInfoMesg, EventMesg, FieldDescriptionMesg, Message, RecordMesg};
+pub use header::HeaderSize;
+pub use repair::{repair, Repair};
+pub use schema::{Component, Field, FieldRangeError, FieldValue, FitString, Mesg};
+pub use validate::{validate, Issue, IssueKind};
+pub use writer::{ByteOrder, Clock, FitWriter, Spool};
@@ -0,0 +1,204 @@
+use std::io::Write;
+
+use crate::profile::{
+    Activity, ActivityMesg, DeveloperDataIdMesg, DeviceInfoMesg, Event, EventMesg, EventType, FieldDescriptionMesg, File,
+    FileIdMesg, LapMesg, Manufacturer, RecordMesg, SessionMesg, Sport, SubSport,
+};
+use crate::echo::write_echo_developer_data;
+use crate::schema::{FieldValue, Mesg};
+use crate::error::FitError;
+use crate::writer::FitWriter;
+
+/// The value of a developer field declared earlier in the file by a
+/// `field_description` message. `value` holds the little-endian encoding of
+/// the field's declared base type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeveloperField {
+    pub developer_data_index: u8,
+    pub field_definition_number: u8,
+    pub value: Vec<u8>,
+}
+
+impl DeveloperField {
+    /// Encodes `value`, which has to match the base type given in the
+    /// field's description.
+    pub fn new<T: FieldValue>(developer_data_index: u8, field_definition_number: u8, value: &T) -> Self {
+        let mut encoded = Vec::new();
+        value.write_to(&mut encoded);
+        DeveloperField { developer_data_index, field_definition_number, value: encoded }
+    }
+}
+
+/// Writes any profile message. The definition holds only the populated
+/// fields of `mesg`; developer fields are appended after the native ones.
+pub fn write_mesg<W: Write, M: Mesg>(fit: &mut FitWriter<W>, mesg: &M, developer_fields: &[DeveloperField]) -> Result<(), FitError> {
+    let mut def: Vec<u8> = vec![0, 0]; // reserved, arch
+    def.extend_from_slice(&M::MESG_NUM.to_le_bytes());
+    def.push(0);
+
+    let mut data: Vec<u8> = Vec::new();
+    mesg.encode(&mut def, &mut data);
+
+    // Field counts and sizes are single bytes; a field longer than 255 bytes
+    // shows up as a size that no longer adds up to the data.
+    let overflow = FitError::DefinitionOverflow { mesg_num: M::MESG_NUM };
+    let num_fields = (def.len() - 5) / 3;
+    let declared_size: usize = def[5..].chunks_exact(3).map(|field| field[1] as usize).sum();
+    if num_fields > u8::MAX as usize || declared_size != data.len() {
+        return Err(overflow);
+    }
+    def[4] = num_fields as u8;
+
+    if !developer_fields.is_empty() {
+        if developer_fields.len() > u8::MAX as usize || developer_fields.iter().any(|field| field.value.len() > u8::MAX as usize) {
+            return Err(overflow);
+        }
+        def.push(developer_fields.len() as u8);
+        for field in developer_fields {
+            def.extend_from_slice(&[field.field_definition_number, field.value.len() as u8, field.developer_data_index]);
+            data.extend_from_slice(&field.value);
+        }
+    }
+
+    fit.write_mesg(&def, &data)
+}
+
+pub fn write_file_id_message<W: Write>(fit: &mut FitWriter<W>) -> Result<(), FitError> {
+    let file_id_mesg = FileIdMesg {
+        serial_number: 3469062800,
+        time_created: fit.now(),
+        product_name: "Echo".into(),
+        manufacturer: Manufacturer::Garmin.into(),
+        product: 4376,
+        type_: File::Activity.into(),
+        ..Default::default()
+    };
+
+    write_mesg(fit, &file_id_mesg, &[])?;
+
+    Ok(())
+}
+
+pub fn write_device_info_message<W: Write>(fit: &mut FitWriter<W>) -> Result<(), FitError> {
+    let mut device_info_mesg = DeviceInfoMesg {
+        device_index: 1,
+        manufacturer: Manufacturer::GarminFr405Antfs.into(),
+        product: 0, // Use a unique ID for each of your products
+        serial_number: 123456,
+        product_name: "Echo Bike".into(),
+        timestamp: fit.now(),
+        ..Default::default()
+    };
+    device_info_mesg.set_software_version(1.0)?;
+
+    write_mesg(fit, &device_info_mesg, &[])
+}
+
+pub fn write_start_event<W: Write>(fit: &mut FitWriter<W>) -> Result<(), FitError> {
+    let mut event_mesg = EventMesg {
+        timestamp: fit.now(),
+        ..Default::default()
+    };
+
+    event_mesg.timestamp = fit.now();
+    event_mesg.event = Event::Timer.into();
+    event_mesg.event_type = EventType::Start.into();
+
+    write_mesg(fit, &event_mesg, &[])?;
+
+    Ok(())
+}
+
+pub fn write_stop_event<W: Write>(fit: &mut FitWriter<W>) -> Result<(), FitError> {
+    let mut event_mesg = EventMesg {
+        timestamp: fit.now(),
+        ..Default::default()
+    };
+
+    event_mesg.timestamp = fit.now();
+    event_mesg.event = Event::Timer.into();
+    event_mesg.event_type = EventType::Stop.into();
+
+    write_mesg(fit, &event_mesg, &[])?;
+
+    Ok(())
+}
+
+pub fn write_record<W: Write>(fit: &mut FitWriter<W>, developer_fields: &[DeveloperField]) -> Result<(), FitError> {
+    let record_mesg = RecordMesg {
+        timestamp: fit.now(),
+        ..Default::default()
+    };
+
+    write_mesg(fit, &record_mesg, developer_fields)
+}
+
+pub fn write_lap<W: Write>(fit: &mut FitWriter<W>, developer_fields: &[DeveloperField]) -> Result<(), FitError> {
+    let start_time = fit.now();
+    let lap_mesg = LapMesg {
+        timestamp: fit.now(),
+        start_time,
+        total_elapsed_time: 0,
+        total_timer_time: 0,
+        ..Default::default()
+    };
+
+    write_mesg(fit, &lap_mesg, developer_fields)
+}
+pub fn write_session<W: Write>(fit: &mut FitWriter<W>, developer_fields: &[DeveloperField]) -> Result<(), FitError> {
+    let start_time = fit.now();
+    let mut session_mesg = SessionMesg {
+        timestamp: fit.now(),
+        start_time,
+        ..Default::default()
+    };
+
+    let elapsed = fit.now().saturating_sub(start_time) as f64;
+    session_mesg.set_total_elapsed_time(elapsed)?;
+    session_mesg.set_total_timer_time(elapsed)?;
+    session_mesg.sport = Sport::Generic.into();
+    session_mesg.sub_sport = SubSport::Generic.into();
+    session_mesg.first_lap_index = 0;
+    session_mesg.num_laps = 1;
+
+    write_mesg(fit, &session_mesg, developer_fields)
+} 
+
+pub fn write_activity<W: Write>(fit: &mut FitWriter<W>) -> Result<(), FitError> {
+    let act_mesg = ActivityMesg {
+        timestamp: fit.now(),
+        total_timer_time: 0,
+        local_timestamp: fit.now(),
+        num_sessions: 1,
+        type_: Activity::Manual.into(),
+        event: Event::Activity.into(),
+        event_type: EventType::Stop.into(),
+        ..Default::default()
+    };
+
+    write_mesg(fit, &act_mesg, &[])
+}
+
+/// Writes a complete activity: file_id, device_info, the Echo developer
+/// data, a timer start, one record, a timer stop, and the lap, session and
+/// activity summaries, all timestamped in order.
+pub fn write_activity_file<W: Write>(fit: &mut FitWriter<W>) -> Result<(), FitError> {
+    write_file_id_message(fit)?;
+    write_device_info_message(fit)?;
+    write_echo_developer_data(fit)?;
+    write_start_event(fit)?;
+    write_record(fit, &[])?;
+    write_stop_event(fit)?;
+    write_lap(fit, &[])?;
+    write_session(fit, &[])?;
+    write_activity(fit)
+}
+
+pub fn write_developer_data_id<W: Write>(fit: &mut FitWriter<W>, mesg: &DeveloperDataIdMesg) -> Result<(), FitError> {
+    write_mesg(fit, mesg, &[])
+}
+
+pub fn write_field_description<W: Write>(fit: &mut FitWriter<W>, mesg: &FieldDescriptionMesg) -> Result<(), FitError> {
+    fit.declare_developer_field(mesg.developer_data_index, mesg.field_definition_number, mesg.fit_base_type_id);
+    write_mesg(fit, mesg, &[])
+}
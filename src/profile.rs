@@ -0,0 +1,13 @@
+//! Messages, field tables and enums generated by `build.rs` from the FIT
+//! profile export in `profile/`.
+//!
+//! `profile/types.csv` and `profile/messages.csv` follow the column layout of
+//! the Types and Messages sheets of the SDK's Profile.xlsx. To model another
+//! message, add its rows there (and its `mesg_num` value to `types.csv`).
+
+#![allow(clippy::enum_variant_names)]
+
+// The generated tables may name any base type constant.
+use crate::schema::*;
+
+include!(concat!(env!("OUT_DIR"), "/profile.rs"));
@@ -0,0 +1,260 @@
+use crate::decoder::{DataMesg, Decoder, Header, Record};
+use crate::error::FitError;
+use crate::messages::write_mesg;
+use crate::profile::{Activity, ActivityMesg, Event, EventType, LapMesg, RecordMesg, SessionMesg, Sport, SubSport};
+use crate::schema::*;
+use crate::writer::FitWriter;
+
+const FILE_CRC_SIZE: usize = 2;
+const TIMESTAMP_FIELD_NUM: u8 = 253;
+
+/// What [`repair`] did to a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Repair {
+    /// The repaired file.
+    pub data: Vec<u8>,
+    /// Messages kept from the original file, definitions included.
+    pub salvaged: usize,
+    /// Bytes dropped from the end of the file, such as a partial message.
+    pub dropped: usize,
+    /// Global message numbers of the summaries that were added.
+    pub synthesized: Vec<u16>,
+}
+
+/// Rebuilds a FIT activity file that was cut short or damaged.
+///
+/// The header's data size is only trusted if it matches a record area that
+/// decodes cleanly; otherwise the records are scanned up to the end of the
+/// file and everything from the first message that cannot be decoded is
+/// dropped. The summaries missing from the end of the file are synthesized
+/// from the record messages that are left: a lap for the records after the
+/// last lap, a session and an activity, leaving out any that the file still
+/// has. The file gets a fresh header and CRC.
+pub fn repair(data: &[u8]) -> Result<Repair, FitError> {
+    let header = Header::parse(data)?;
+    let start = header.header_size as usize;
+    let (end, consumed, mesgs) = salvage(data, header);
+    let salvaged = mesgs.len();
+    if !mesgs.iter().any(|record| matches!(record, Record::Data(_))) {
+        return Err(FitError::InvalidState("no complete messages to salvage".to_string()));
+    }
+
+    let mut fit = FitWriter::buffered(Vec::new());
+    fit.write_records(&data[start..end], header.protocol_version)?;
+
+    let data_mesgs: Vec<&DataMesg> = mesgs
+        .iter()
+        .filter_map(|record| match record {
+            Record::Data(mesg) => Some(mesg),
+            Record::Definition(_) => None,
+        })
+        .collect();
+    let count = |mesg_num: u16| data_mesgs.iter().filter(|mesg| mesg.mesg_num == mesg_num).count();
+    let after_last = |mesg_num: u16| data_mesgs.iter().rposition(|mesg| mesg.mesg_num == mesg_num).map_or(0, |i| i + 1);
+    let mut synthesized = Vec::new();
+
+    // Summaries close the file in lap, session, activity order, so a cut
+    // only ever loses the ones after the last summary that made it. A file
+    // that still has its activity needs nothing, and one that has a session
+    // only the activity.
+    if count(ActivityMesg::MESG_NUM) == 0 {
+        let mut num_sessions = count(SessionMesg::MESG_NUM);
+        if num_sessions == 0 {
+            // The last lap runs from the end of the one before it, if any.
+            let mut num_laps = count(LapMesg::MESG_NUM);
+            let (earlier, lap_records) = data_mesgs.split_at(after_last(LapMesg::MESG_NUM));
+            if num_laps == 0 || lap_records.iter().any(|mesg| mesg.mesg_num == RecordMesg::MESG_NUM) {
+                let start_distance = Summary::new(earlier).distance;
+                write_mesg(&mut fit, &Summary::new(lap_records).lap(num_laps, start_distance), &[])?;
+                synthesized.push(LapMesg::MESG_NUM);
+                num_laps += 1;
+            }
+            write_mesg(&mut fit, &Summary::new(&data_mesgs).session(num_laps), &[])?;
+            synthesized.push(SessionMesg::MESG_NUM);
+            num_sessions = 1;
+        }
+        write_mesg(&mut fit, &Summary::new(&data_mesgs).activity(num_sessions), &[])?;
+        synthesized.push(ActivityMesg::MESG_NUM);
+    }
+
+    Ok(Repair { data: fit.finish()?, salvaged, dropped: data.len() - consumed, synthesized })
+}
+
+// The complete messages at the start of the record area, where they end,
+// and where the part of the file they account for ends, old CRC included.
+fn salvage(data: &[u8], header: Header) -> (usize, usize, Vec<Record>) {
+    let start = header.header_size as usize;
+    let declared_end = start.saturating_add(header.data_size as usize);
+    if header.data_size > 0 && declared_end.saturating_add(FILE_CRC_SIZE) <= data.len() {
+        let (end, records) = scan(&data[..declared_end], header);
+        if end == declared_end {
+            return (end, end + FILE_CRC_SIZE, records);
+        }
+    }
+
+    // The data size is stale, e.g. still the placeholder written when the
+    // file was opened, so take everything up to the end of the file.
+    let data_size = u32::try_from(data.len() - start).unwrap_or(u32::MAX);
+    let (end, records) = scan(data, Header { data_size, ..header });
+    (end, end, records)
+}
+
+fn scan(data: &[u8], header: Header) -> (usize, Vec<Record>) {
+    let mut decoder = Decoder::with_header(data, header);
+    let mut records = Vec::new();
+    let mut end = decoder.offset();
+    while let Ok(Some(record)) = decoder.next_record() {
+        records.push(record);
+        end = decoder.offset();
+    }
+    (end, records)
+}
+
+// Average and maximum of the valid values of one record field.
+#[derive(Default)]
+struct Stat {
+    sum: u64,
+    count: u64,
+    max: u32,
+}
+
+impl Stat {
+    fn add<T: FieldValue + Into<u32>>(&mut self, value: T, base_type: u8) {
+        if !value.is_invalid(base_type) {
+            let value = value.into();
+            self.sum += value as u64;
+            self.count += 1;
+            self.max = self.max.max(value);
+        }
+    }
+
+    fn avg<T: FieldValue + TryFrom<u32>>(&self, base_type: u8) -> T {
+        match self.count {
+            0 => T::invalid(base_type),
+            count => T::try_from((self.sum / count) as u32).unwrap_or_else(|_| T::invalid(base_type)),
+        }
+    }
+
+    fn max<T: FieldValue + TryFrom<u32>>(&self, base_type: u8) -> T {
+        match self.count {
+            0 => T::invalid(base_type),
+            _ => T::try_from(self.max).unwrap_or_else(|_| T::invalid(base_type)),
+        }
+    }
+}
+
+// Totals over the record messages, for the summaries a file is missing.
+#[derive(Default)]
+struct Summary {
+    start_time: Option<u32>,
+    end_time: Option<u32>,
+    // First and last cumulative distance.
+    first_distance: Option<u32>,
+    distance: Option<u32>,
+    heart_rate: Stat,
+    cadence: Stat,
+    power: Stat,
+    speed: Stat,
+}
+
+// Lap and session share these fields, down to their scales.
+macro_rules! set_summary_fields {
+    ($mesg:ident, $summary:expr) => {
+        let summary = $summary;
+        $mesg.timestamp = summary.end_time.unwrap_or(FIT_UINT32_INVALID);
+        $mesg.start_time = summary.start_time.unwrap_or(FIT_UINT32_INVALID);
+        $mesg.set_total_elapsed_time_saturating(summary.elapsed_time());
+        $mesg.set_total_timer_time_saturating(summary.elapsed_time());
+        $mesg.total_distance = summary.distance.unwrap_or(FIT_UINT32_INVALID);
+        $mesg.avg_heart_rate = summary.heart_rate.avg(BASE_TYPE_UINT8);
+        $mesg.max_heart_rate = summary.heart_rate.max(BASE_TYPE_UINT8);
+        $mesg.avg_cadence = summary.cadence.avg(BASE_TYPE_UINT8);
+        $mesg.max_cadence = summary.cadence.max(BASE_TYPE_UINT8);
+        $mesg.avg_power = summary.power.avg(BASE_TYPE_UINT16);
+        $mesg.max_power = summary.power.max(BASE_TYPE_UINT16);
+        $mesg.avg_speed = summary.speed.avg(BASE_TYPE_UINT16);
+        $mesg.max_speed = summary.speed.max(BASE_TYPE_UINT16);
+    };
+}
+
+impl Summary {
+    fn new(mesgs: &[&DataMesg]) -> Self {
+        let mut summary = Summary::default();
+        for record in mesgs.iter().filter_map(|mesg| mesg.decode::<RecordMesg>()) {
+            if record.timestamp != FIT_UINT32_INVALID {
+                summary.start_time.get_or_insert(record.timestamp);
+                summary.end_time = Some(record.timestamp);
+            }
+            if record.distance != FIT_UINT32_INVALID {
+                summary.first_distance.get_or_insert(record.distance);
+                summary.distance = Some(record.distance);
+            }
+            summary.heart_rate.add(record.heart_rate, BASE_TYPE_UINT8);
+            summary.cadence.add(record.cadence, BASE_TYPE_UINT8);
+            summary.power.add(record.power, BASE_TYPE_UINT16);
+            summary.speed.add(record.speed, BASE_TYPE_UINT16);
+        }
+        // A timer stop or another event can come after the last record, and
+        // the summaries must not end before it.
+        for mesg in mesgs {
+            let timestamp = mesg.compressed_timestamp.or_else(|| {
+                let field = mesg.fields.iter().find(|field| field.num == TIMESTAMP_FIELD_NUM && field.base_type == BASE_TYPE_UINT32)?;
+                Some(u32::from_le_bytes(field.value.get(..4)?.try_into().unwrap()))
+            });
+            summary.end_time = summary.end_time.max(timestamp.filter(|&timestamp| timestamp != FIT_UINT32_INVALID));
+        }
+        summary
+    }
+
+    fn elapsed_time(&self) -> f64 {
+        match (self.start_time, self.end_time) {
+            (Some(start), Some(end)) => end.saturating_sub(start) as f64,
+            _ => f64::NAN,
+        }
+    }
+
+    // `start_distance` is where the lap before ended, if there was one.
+    fn lap(&self, message_index: usize, start_distance: Option<u32>) -> LapMesg {
+        let mut lap = LapMesg {
+            message_index: u16::try_from(message_index).unwrap_or(FIT_UINT16_INVALID),
+            event: Event::Lap.into(),
+            event_type: EventType::Stop.into(),
+            sport: Sport::Generic.into(),
+            ..Default::default()
+        };
+        set_summary_fields!(lap, self);
+        // Record distances are cumulative; the lap only covers its own.
+        lap.total_distance = match (start_distance.or(self.first_distance), self.distance) {
+            (Some(start), Some(end)) => end.saturating_sub(start),
+            _ => FIT_UINT32_INVALID,
+        };
+        lap
+    }
+
+    fn session(&self, num_laps: usize) -> SessionMesg {
+        let mut session = SessionMesg {
+            event: Event::Session.into(),
+            event_type: EventType::Stop.into(),
+            sport: Sport::Generic.into(),
+            sub_sport: SubSport::Generic.into(),
+            first_lap_index: 0,
+            num_laps: u16::try_from(num_laps).unwrap_or(FIT_UINT16_INVALID),
+            ..Default::default()
+        };
+        set_summary_fields!(session, self);
+        session
+    }
+
+    fn activity(&self, num_sessions: usize) -> ActivityMesg {
+        let mut activity = ActivityMesg {
+            timestamp: self.end_time.unwrap_or(FIT_UINT32_INVALID),
+            num_sessions: u16::try_from(num_sessions).unwrap_or(FIT_UINT16_INVALID),
+            type_: Activity::Manual.into(),
+            event: Event::Activity.into(),
+            event_type: EventType::Stop.into(),
+            ..Default::default()
+        };
+        activity.set_total_timer_time_saturating(self.elapsed_time());
+        activity
+    }
+}
@@ -0,0 +1,534 @@
+use std::error::Error;
+use std::fmt;
+
+pub const BASE_TYPE_ENUM: u8 = 0x00;
+pub const BASE_TYPE_SINT8: u8 = 0x01;
+pub const BASE_TYPE_UINT8: u8 = 0x02;
+pub const BASE_TYPE_SINT16: u8 = 0x83;
+pub const BASE_TYPE_UINT16: u8 = 0x84;
+pub const BASE_TYPE_SINT32: u8 = 0x85;
+pub const BASE_TYPE_UINT32: u8 = 0x86;
+pub const BASE_TYPE_STRING: u8 = 0x07;
+pub const BASE_TYPE_UINT8Z: u8 = 0x0A;
+pub const BASE_TYPE_UINT16Z: u8 = 0x8B;
+pub const BASE_TYPE_UINT32Z: u8 = 0x8C;
+pub const BASE_TYPE_BYTE: u8 = 0x0D;
+pub const BASE_TYPE_FLOAT32: u8 = 0x88;
+pub const BASE_TYPE_FLOAT64: u8 = 0x89;
+pub const BASE_TYPE_SINT64: u8 = 0x8E;
+pub const BASE_TYPE_UINT64: u8 = 0x8F;
+pub const BASE_TYPE_UINT64Z: u8 = 0x90;
+// Base types with this bit set have multi-byte elements that follow the
+// definition's architecture.
+const BASE_TYPE_ENDIAN_FLAG: u8 = 0x80;
+
+pub const FIT_ENUM_INVALID: u8 = 0xFF;
+pub const FIT_SINT8_INVALID: i8 = 0x7F;
+pub const FIT_UINT8_INVALID: u8 = 0xFF;
+pub const FIT_SINT16_INVALID: i16 = 0x7FFF;
+pub const FIT_UINT16_INVALID: u16 = 0xFFFF;
+pub const FIT_SINT32_INVALID: i32 = 0x7FFFFFFF;
+pub const FIT_UINT32_INVALID: u32 = 0xFFFFFFFF;
+pub const FIT_STRING_INVALID: u8 = 0x00;
+pub const FIT_UINT8Z_INVALID: u8 = 0x00;
+pub const FIT_UINT16Z_INVALID: u16 = 0x0000;
+pub const FIT_UINT32Z_INVALID: u32 = 0x00000000;
+pub const FIT_BYTE_INVALID: u8 = 0xFF;
+pub const FIT_FLOAT32_INVALID: u32 = 0xFFFFFFFF; // bit pattern
+pub const FIT_FLOAT64_INVALID: u64 = 0xFFFFFFFFFFFFFFFF; // bit pattern
+pub const FIT_SINT64_INVALID: i64 = 0x7FFFFFFFFFFFFFFF;
+pub const FIT_UINT64_INVALID: u64 = 0xFFFFFFFFFFFFFFFF;
+pub const FIT_UINT64Z_INVALID: u64 = 0x0000000000000000;
+
+/// Size in bytes of a single element of `base_type`.
+pub const fn base_type_size(base_type: u8) -> u8 {
+    match base_type {
+        BASE_TYPE_SINT16 | BASE_TYPE_UINT16 | BASE_TYPE_UINT16Z => 2,
+        BASE_TYPE_SINT32 | BASE_TYPE_UINT32 | BASE_TYPE_UINT32Z | BASE_TYPE_FLOAT32 => 4,
+        BASE_TYPE_SINT64 | BASE_TYPE_UINT64 | BASE_TYPE_UINT64Z | BASE_TYPE_FLOAT64 => 8,
+        _ => 1,
+    }
+}
+
+/// Whether `base_type` is one of the base types defined by the SDK.
+pub const fn is_base_type(base_type: u8) -> bool {
+    matches!(
+        base_type,
+        BASE_TYPE_ENUM
+            | BASE_TYPE_SINT8
+            | BASE_TYPE_UINT8
+            | BASE_TYPE_SINT16
+            | BASE_TYPE_UINT16
+            | BASE_TYPE_SINT32
+            | BASE_TYPE_UINT32
+            | BASE_TYPE_STRING
+            | BASE_TYPE_FLOAT32
+            | BASE_TYPE_FLOAT64
+            | BASE_TYPE_UINT8Z
+            | BASE_TYPE_UINT16Z
+            | BASE_TYPE_UINT32Z
+            | BASE_TYPE_BYTE
+            | BASE_TYPE_SINT64
+            | BASE_TYPE_UINT64
+            | BASE_TYPE_UINT64Z
+    )
+}
+
+/// Profile name of `base_type`, e.g. `"uint16"`.
+pub const fn base_type_name(base_type: u8) -> &'static str {
+    match base_type {
+        BASE_TYPE_ENUM => "enum",
+        BASE_TYPE_SINT8 => "sint8",
+        BASE_TYPE_UINT8 => "uint8",
+        BASE_TYPE_SINT16 => "sint16",
+        BASE_TYPE_UINT16 => "uint16",
+        BASE_TYPE_SINT32 => "sint32",
+        BASE_TYPE_UINT32 => "uint32",
+        BASE_TYPE_STRING => "string",
+        BASE_TYPE_FLOAT32 => "float32",
+        BASE_TYPE_FLOAT64 => "float64",
+        BASE_TYPE_UINT8Z => "uint8z",
+        BASE_TYPE_UINT16Z => "uint16z",
+        BASE_TYPE_UINT32Z => "uint32z",
+        BASE_TYPE_BYTE => "byte",
+        BASE_TYPE_SINT64 => "sint64",
+        BASE_TYPE_UINT64 => "uint64",
+        BASE_TYPE_UINT64Z => "uint64z",
+        _ => "unknown",
+    }
+}
+
+/// Whether `base_type` only exists from protocol version 2.0 on.
+pub const fn is_protocol_2_base_type(base_type: u8) -> bool {
+    matches!(base_type, BASE_TYPE_SINT64 | BASE_TYPE_UINT64 | BASE_TYPE_UINT64Z)
+}
+
+/// Profile metadata for one field of a message.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Field {
+    pub num: u8,
+    pub name: &'static str,
+    /// Profile type of the field, e.g. `"sport"`, or its base type name.
+    pub profile_type: &'static str,
+    pub base_type: u8,
+    pub scale: f64,
+    pub offset: f64,
+    pub units: &'static str,
+}
+
+/// One value packed into a component field, such as the speed half of
+/// `compressed_speed_distance`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Component {
+    /// Field of the same message the value expands to.
+    pub field_num: u8,
+    pub name: &'static str,
+    pub scale: f64,
+    pub offset: f64,
+    pub units: &'static str,
+    pub bits: u32,
+    /// The value is the low `bits` of a running total.
+    pub accumulate: bool,
+}
+
+/// A message declared with [`fit_message!`].
+pub trait Mesg: Default {
+    const MESG_NUM: u16;
+    const FIELDS: &'static [Field];
+
+    /// Appends a (number, size, base type) definition and the data of every
+    /// populated field.
+    fn encode(&self, def: &mut Vec<u8>, data: &mut Vec<u8>);
+
+    /// Sets field `num` from its little-endian encoding. Returns false, and
+    /// leaves the message as it was, for unknown fields and values whose
+    /// size does not suit the field.
+    fn set_field(&mut self, num: u8, value: &[u8]) -> bool;
+}
+
+/// Builds a message from (field number, little-endian value) pairs, skipping
+/// the fields it cannot take.
+pub fn mesg_from_fields<'a, M: Mesg>(fields: impl IntoIterator<Item = (u8, &'a [u8])>) -> M {
+    let mut mesg = M::default();
+    for (num, value) in fields {
+        mesg.set_field(num, value);
+    }
+    mesg
+}
+
+/// Reverses the byte order of every element of a field value in place.
+/// Single-byte base types are left alone.
+pub fn swap_byte_order(value: &mut [u8], base_type: u8) {
+    let element_size = base_type_size(base_type) as usize;
+    if base_type & BASE_TYPE_ENDIAN_FLAG != 0 && element_size > 1 {
+        for element in value.chunks_exact_mut(element_size) {
+            element.reverse();
+        }
+    }
+}
+
+/// A Rust type that can hold a FIT field of a matching base type.
+pub trait FieldValue {
+    const ELEMENT_SIZE: u8;
+
+    fn invalid(base_type: u8) -> Self;
+    fn is_invalid(&self, base_type: u8) -> bool;
+    fn write_to(&self, data: &mut Vec<u8>);
+    fn size(&self) -> usize;
+    /// Parses a little-endian value, or None if `value` has the wrong size.
+    fn read_from(value: &[u8], base_type: u8) -> Option<Self>
+    where
+        Self: Sized;
+}
+
+macro_rules! impl_field_value {
+    ($ty:ty, $($base_type:ident => $invalid:expr),* ; _ => $default:expr) => {
+        impl FieldValue for $ty {
+            const ELEMENT_SIZE: u8 = size_of::<$ty>() as u8;
+
+            fn invalid(base_type: u8) -> Self {
+                match base_type {
+                    $($base_type => $invalid,)*
+                    _ => $default,
+                }
+            }
+
+            fn is_invalid(&self, base_type: u8) -> bool {
+                *self == Self::invalid(base_type)
+            }
+
+            fn write_to(&self, data: &mut Vec<u8>) {
+                data.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn size(&self) -> usize {
+                size_of::<$ty>()
+            }
+
+            fn read_from(value: &[u8], _base_type: u8) -> Option<Self> {
+                Some(<$ty>::from_le_bytes(value.try_into().ok()?))
+            }
+        }
+    };
+}
+
+impl_field_value!(u8, BASE_TYPE_UINT8Z => FIT_UINT8Z_INVALID, BASE_TYPE_STRING => FIT_STRING_INVALID; _ => FIT_UINT8_INVALID);
+impl_field_value!(i8, ; _ => FIT_SINT8_INVALID);
+impl_field_value!(u16, BASE_TYPE_UINT16Z => FIT_UINT16Z_INVALID; _ => FIT_UINT16_INVALID);
+impl_field_value!(i16, ; _ => FIT_SINT16_INVALID);
+impl_field_value!(u32, BASE_TYPE_UINT32Z => FIT_UINT32Z_INVALID; _ => FIT_UINT32_INVALID);
+impl_field_value!(i32, ; _ => FIT_SINT32_INVALID);
+impl_field_value!(u64, BASE_TYPE_UINT64Z => FIT_UINT64Z_INVALID; _ => FIT_UINT64_INVALID);
+impl_field_value!(i64, ; _ => FIT_SINT64_INVALID);
+
+// Invalid floats are an all-ones NaN, so they compare by bit pattern.
+macro_rules! impl_float_field_value {
+    ($ty:ty, $invalid:expr) => {
+        impl FieldValue for $ty {
+            const ELEMENT_SIZE: u8 = size_of::<$ty>() as u8;
+
+            fn invalid(_base_type: u8) -> Self {
+                <$ty>::from_bits($invalid)
+            }
+
+            fn is_invalid(&self, _base_type: u8) -> bool {
+                self.to_bits() == $invalid
+            }
+
+            fn write_to(&self, data: &mut Vec<u8>) {
+                data.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn size(&self) -> usize {
+                size_of::<$ty>()
+            }
+
+            fn read_from(value: &[u8], _base_type: u8) -> Option<Self> {
+                Some(<$ty>::from_le_bytes(value.try_into().ok()?))
+            }
+        }
+    };
+}
+
+impl_float_field_value!(f32, FIT_FLOAT32_INVALID);
+impl_float_field_value!(f64, FIT_FLOAT64_INVALID);
+
+/// A numeric field that physical values can be scaled into.
+pub trait ScalarValue: FieldValue + Copy {
+    /// Smallest and largest raw value of `base_type`, leaving out the
+    /// invalid value.
+    fn raw_range(base_type: u8) -> (f64, f64);
+    /// Rounds a scaled value to what the field can represent.
+    fn quantize(raw: f64) -> f64;
+    /// Converts a quantized value that lies within `raw_range`.
+    fn from_raw(raw: f64, base_type: u8) -> Self;
+}
+
+macro_rules! impl_scalar_value {
+    ($($ty:ty),*) => {$(
+        impl ScalarValue for $ty {
+            fn raw_range(base_type: u8) -> (f64, f64) {
+                let (min, max) = <$ty>::valid_range(base_type);
+                (min as f64, max as f64)
+            }
+
+            fn quantize(raw: f64) -> f64 {
+                raw.round()
+            }
+
+            fn from_raw(raw: f64, base_type: u8) -> Self {
+                // 64-bit bounds are not exact as f64, so clamp once more
+                // after the (saturating) cast.
+                let (min, max) = <$ty>::valid_range(base_type);
+                (raw as $ty).clamp(min, max)
+            }
+        }
+
+        impl ValidRange for $ty {
+            fn valid_range(base_type: u8) -> ($ty, $ty) {
+                // The invalid value sits at one end of the range.
+                if Self::invalid(base_type) == <$ty>::MAX {
+                    (<$ty>::MIN, <$ty>::MAX - 1)
+                } else {
+                    (<$ty>::MIN + 1, <$ty>::MAX)
+                }
+            }
+        }
+    )*};
+}
+
+trait ValidRange: Sized {
+    fn valid_range(base_type: u8) -> (Self, Self);
+}
+
+impl_scalar_value!(u8, i8, u16, i16, u32, i32, u64, i64);
+
+macro_rules! impl_float_scalar_value {
+    ($($ty:ty),*) => {$(
+        impl ScalarValue for $ty {
+            fn raw_range(_base_type: u8) -> (f64, f64) {
+                (<$ty>::MIN as f64, <$ty>::MAX as f64)
+            }
+
+            fn quantize(raw: f64) -> f64 {
+                raw
+            }
+
+            fn from_raw(raw: f64, _base_type: u8) -> Self {
+                raw as $ty
+            }
+        }
+    )*};
+}
+
+impl_float_scalar_value!(f32, f64);
+
+/// A physical value that does not fit the raw range of its field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FieldRangeError {
+    pub field: &'static str,
+    pub value: f64,
+}
+
+impl fmt::Display for FieldRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is out of range for field {}", self.value, self.field)
+    }
+}
+
+impl Error for FieldRangeError {}
+
+// FIT defines physical = raw / scale - offset.
+fn scale_value<T: ScalarValue>(value: f64, scale: f64, offset: f64) -> f64 {
+    T::quantize((value + offset) * scale)
+}
+
+/// Converts a physical value to the raw value of a field with the given
+/// base type, scale and offset.
+pub fn to_raw<T: ScalarValue>(field: &'static str, value: f64, base_type: u8, scale: f64, offset: f64) -> Result<T, FieldRangeError> {
+    let raw = scale_value::<T>(value, scale, offset);
+    let (min, max) = T::raw_range(base_type);
+    if (min..=max).contains(&raw) {
+        Ok(T::from_raw(raw, base_type))
+    } else {
+        Err(FieldRangeError { field, value })
+    }
+}
+
+/// Like [`to_raw`], but clamps to the nearest valid raw value. NaN leaves
+/// the field invalid.
+pub fn to_raw_saturating<T: ScalarValue>(value: f64, base_type: u8, scale: f64, offset: f64) -> T {
+    if value.is_nan() {
+        return T::invalid(base_type);
+    }
+    let (min, max) = T::raw_range(base_type);
+    T::from_raw(scale_value::<T>(value, scale, offset).clamp(min, max), base_type)
+}
+
+// Byte arrays back multi-element fields such as speed_1s.
+impl<const N: usize> FieldValue for [u8; N] {
+    const ELEMENT_SIZE: u8 = 1;
+
+    fn invalid(base_type: u8) -> Self {
+        [u8::invalid(base_type); N]
+    }
+
+    fn is_invalid(&self, base_type: u8) -> bool {
+        self.iter().all(|b| b.is_invalid(base_type))
+    }
+
+    fn write_to(&self, data: &mut Vec<u8>) {
+        data.extend_from_slice(self);
+    }
+
+    fn size(&self) -> usize {
+        N
+    }
+
+    // Other encoders may use a different array length; keep what fits.
+    fn read_from(value: &[u8], base_type: u8) -> Option<Self> {
+        let mut array = Self::invalid(base_type);
+        let length = value.len().min(N);
+        array[..length].copy_from_slice(&value[..length]);
+        Some(array)
+    }
+}
+
+/// A UTF-8 string field.
+///
+/// It is written as the encoded string plus a null terminator, so the field
+/// size in the definition follows the string. Strings longer than
+/// [`FitString::MAX_LEN`] bytes are cut at the last character boundary that
+/// fits, and anything after an embedded null is dropped.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FitString(String);
+
+impl FitString {
+    /// Longest string that fits the one byte field size with its terminator.
+    pub const MAX_LEN: usize = 254;
+
+    pub fn new(s: &str) -> Self {
+        let s = s.split('\0').next().unwrap_or_default();
+        let mut length = s.len().min(Self::MAX_LEN);
+        while !s.is_char_boundary(length) {
+            length -= 1;
+        }
+        FitString(s[..length].to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for FitString {
+    fn from(s: &str) -> Self {
+        FitString::new(s)
+    }
+}
+
+impl FieldValue for FitString {
+    const ELEMENT_SIZE: u8 = 1;
+
+    fn invalid(_base_type: u8) -> Self {
+        FitString::default()
+    }
+
+    fn is_invalid(&self, _base_type: u8) -> bool {
+        self.0.is_empty()
+    }
+
+    fn write_to(&self, data: &mut Vec<u8>) {
+        data.extend_from_slice(self.0.as_bytes());
+        data.push(FIT_STRING_INVALID);
+    }
+
+    fn size(&self) -> usize {
+        self.0.len() + 1
+    }
+
+    fn read_from(value: &[u8], _base_type: u8) -> Option<Self> {
+        Some(FitString::new(&String::from_utf8_lossy(value)))
+    }
+}
+
+#[doc(hidden)]
+pub fn encode_field<T: FieldValue>(value: &T, num: u8, base_type: u8, def: &mut Vec<u8>, data: &mut Vec<u8>) {
+    if !value.is_invalid(base_type) {
+        def.extend_from_slice(&[num, value.size() as u8, base_type]);
+        value.write_to(data);
+    }
+}
+
+/// Declares a FIT message from a schema table.
+///
+/// Each line pairs a struct field with its field number, profile name,
+/// profile type, base type, scale, offset and units. The struct, its
+/// all-invalid `Default`, the [`Mesg`] field table and the serializer are all
+/// generated from that one line, and a field whose Rust type does not match
+/// its base type fails to compile. `build.rs` emits these from the bundled
+/// profile; see `src/profile.rs`.
+macro_rules! fit_message {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident: $mesg_num:literal {
+            $(
+                $(#[$field_meta:meta])*
+                $field:ident: $ty:ty = ($num:literal, $field_name:literal, $profile_type:literal, $base_type:ident, $scale:literal, $offset:literal, $units:literal),
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, PartialEq)]
+        $vis struct $name {
+            $($(#[$field_meta])* pub $field: $ty,)*
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                $name {
+                    $($field: <$ty as $crate::schema::FieldValue>::invalid($base_type),)*
+                }
+            }
+        }
+
+        impl $crate::schema::Mesg for $name {
+            const MESG_NUM: u16 = $mesg_num;
+            const FIELDS: &'static [$crate::schema::Field] = &[
+                $($crate::schema::Field {
+                    num: $num,
+                    name: $field_name,
+                    profile_type: $profile_type,
+                    base_type: $base_type,
+                    scale: $scale as f64,
+                    offset: $offset as f64,
+                    units: $units,
+                },)*
+            ];
+
+            fn encode(&self, def: &mut Vec<u8>, data: &mut Vec<u8>) {
+                $($crate::schema::encode_field(&self.$field, $num, $base_type, def, data);)*
+            }
+
+            fn set_field(&mut self, num: u8, value: &[u8]) -> bool {
+                match num {
+                    $($num => match <$ty as $crate::schema::FieldValue>::read_from(value, $base_type) {
+                        Some(value) => {
+                            self.$field = value;
+                            true
+                        }
+                        None => false,
+                    },)*
+                    _ => false,
+                }
+            }
+        }
+
+        $(const _: () = assert!(
+            <$ty as $crate::schema::FieldValue>::ELEMENT_SIZE == $crate::schema::base_type_size($base_type),
+            concat!(stringify!($name), ".", stringify!($field), " does not match its base type"),
+        );)*
+    };
+}
+
+pub(crate) use fit_message;
@@ -0,0 +1,178 @@
+use std::fmt;
+
+use crate::decoder::{DecodeError, Decoder, Definition, Header, Record};
+use crate::profile::{ActivityMesg, FileIdMesg, SessionMesg};
+use crate::schema::{base_type_size, is_base_type, Mesg, BASE_TYPE_UINT32, FIT_UINT32_INVALID};
+
+const FILE_CRC_SIZE: usize = 2;
+const TIMESTAMP_FIELD_NUM: u8 = 253;
+
+/// Something wrong with a FIT file, found by [`validate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Issue {
+    /// Byte offset in the file the issue was found at.
+    pub offset: usize,
+    pub kind: IssueKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IssueKind {
+    /// The file could not be read past this point, or a CRC is wrong.
+    Decode(DecodeError),
+    /// The header's data size runs past the end of the file.
+    Truncated { data_size: u32, file_len: usize },
+    /// Bytes follow the file CRC, e.g. a second chained FIT file.
+    TrailingBytes { len: usize },
+    UnknownBaseType { mesg_num: u16, field_num: u8, base_type: u8 },
+    /// A field's size is not a whole number of elements of its base type.
+    FieldSizeMismatch { mesg_num: u16, field_num: u8, size: u8, base_type: u8 },
+    /// A timestamp is earlier than the one before it.
+    TimestampBackwards { timestamp: u32, previous: u32 },
+    FileIdNotFirst,
+    ActivityNotLast,
+    MissingSession,
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IssueKind::Decode(error) => error.fmt(f),
+            IssueKind::Truncated { data_size, file_len } => {
+                write!(f, "header declares {data_size} bytes of records, but the file is only {file_len} bytes long")
+            }
+            IssueKind::TrailingBytes { len } => write!(f, "{len} bytes after the file CRC"),
+            IssueKind::UnknownBaseType { mesg_num, field_num, base_type } => {
+                write!(f, "field {field_num} of message {mesg_num} has unknown base type {base_type:#04x}")
+            }
+            IssueKind::FieldSizeMismatch { mesg_num, field_num, size, base_type } => write!(
+                f,
+                "field {field_num} of message {mesg_num} is {size} bytes, not a multiple of base type {base_type:#04x}"
+            ),
+            IssueKind::TimestampBackwards { timestamp, previous } => {
+                write!(f, "timestamp {timestamp} is earlier than the previous timestamp {previous}")
+            }
+            IssueKind::FileIdNotFirst => f.write_str("first data message is not file_id"),
+            IssueKind::ActivityNotLast => f.write_str("last data message is not activity"),
+            IssueKind::MissingSession => f.write_str("no session message"),
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "byte {}: {}", self.offset, self.kind)
+    }
+}
+
+/// Checks a FIT activity file for the problems that make uploads fail.
+///
+/// Unlike [`decode`](crate::decode), this keeps going after the first
+/// problem where it can, and reports every issue with its byte offset. An
+/// empty list means the file is valid.
+pub fn validate(data: &[u8]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let header = match Header::parse(data) {
+        Ok(header) => header,
+        Err(error) => {
+            issues.push(Issue { offset: 0, kind: IssueKind::Decode(error) });
+            return issues;
+        }
+    };
+    if let Err(error) = header.check_crc(data) {
+        issues.push(Issue { offset: 0, kind: IssueKind::Decode(error) });
+    }
+
+    let end = (header.header_size as usize).saturating_add(header.data_size as usize);
+    let file_end = end.saturating_add(FILE_CRC_SIZE);
+    if data.len() < file_end {
+        issues.push(Issue { offset: 4, kind: IssueKind::Truncated { data_size: header.data_size, file_len: data.len() } });
+    } else if data.len() > file_end {
+        issues.push(Issue { offset: file_end, kind: IssueKind::TrailingBytes { len: data.len() - file_end } });
+    }
+
+    let mut decoder = Decoder::with_header(data, header);
+    let mut previous_timestamp = None;
+    let mut first_mesg = None;
+    let mut last_mesg = None;
+    let mut has_session = false;
+    loop {
+        let offset = decoder.offset();
+        let mesg = match decoder.next_record() {
+            Ok(Some(Record::Definition(definition))) => {
+                check_definition(&definition, offset, &mut issues);
+                continue;
+            }
+            Ok(Some(Record::Data(mesg))) => mesg,
+            Ok(None) => break,
+            Err(error) => {
+                // Message boundaries are lost, so nothing further can be
+                // checked reliably.
+                let offset = error_offset(&error).unwrap_or(offset);
+                issues.push(Issue { offset, kind: IssueKind::Decode(error) });
+                return issues;
+            }
+        };
+
+        let timestamp = mesg.compressed_timestamp.or_else(|| {
+            let field = mesg.fields.iter().find(|field| field.num == TIMESTAMP_FIELD_NUM && field.base_type == BASE_TYPE_UINT32)?;
+            Some(u32::from_le_bytes(field.value.get(..4)?.try_into().unwrap()))
+        });
+        if let Some(timestamp) = timestamp.filter(|&timestamp| timestamp != FIT_UINT32_INVALID) {
+            if let Some(previous) = previous_timestamp.filter(|&previous| timestamp < previous) {
+                issues.push(Issue { offset, kind: IssueKind::TimestampBackwards { timestamp, previous } });
+            }
+            previous_timestamp = Some(timestamp);
+        }
+
+        first_mesg.get_or_insert((offset, mesg.mesg_num));
+        last_mesg = Some((offset, mesg.mesg_num));
+        has_session |= mesg.mesg_num == SessionMesg::MESG_NUM;
+    }
+
+    if data.len() >= file_end {
+        if let Err(error) = decoder.check_crc() {
+            issues.push(Issue { offset: end, kind: IssueKind::Decode(error) });
+        }
+    }
+
+    match first_mesg {
+        Some((_, FileIdMesg::MESG_NUM)) => {}
+        Some((offset, _)) => issues.push(Issue { offset, kind: IssueKind::FileIdNotFirst }),
+        None => issues.push(Issue { offset: end, kind: IssueKind::FileIdNotFirst }),
+    }
+    match last_mesg {
+        Some((_, ActivityMesg::MESG_NUM)) => {}
+        Some((offset, _)) => issues.push(Issue { offset, kind: IssueKind::ActivityNotLast }),
+        None => issues.push(Issue { offset: end, kind: IssueKind::ActivityNotLast }),
+    }
+    if !has_session {
+        issues.push(Issue { offset: end, kind: IssueKind::MissingSession });
+    }
+    issues
+}
+
+fn check_definition(definition: &Definition, offset: usize, issues: &mut Vec<Issue>) {
+    let mesg_num = definition.mesg_num;
+    for field in &definition.fields {
+        let (field_num, size, base_type) = (field.num, field.size, field.base_type);
+        let kind = if !is_base_type(base_type) {
+            IssueKind::UnknownBaseType { mesg_num, field_num, base_type }
+        } else if size == 0 || size % base_type_size(base_type) != 0 {
+            IssueKind::FieldSizeMismatch { mesg_num, field_num, size, base_type }
+        } else {
+            continue;
+        };
+        issues.push(Issue { offset, kind });
+    }
+}
+
+// Errors that carry a more precise offset than the start of the message.
+fn error_offset(error: &DecodeError) -> Option<usize> {
+    match *error {
+        DecodeError::UnexpectedEof { offset }
+        | DecodeError::MalformedDefinition { offset }
+        | DecodeError::UndefinedLocalMesg { offset, .. }
+        | DecodeError::MissingTimestamp { offset } => Some(offset),
+        _ => None,
+    }
+}
@@ -0,0 +1,502 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::crc::{crc_calc16, crc_combine16, crc_update16};
+use crate::error::FitError;
+use crate::header::{FileHeader, HeaderSize, PROFILE_VERSION, PROTOCOL_VERSION_1, PROTOCOL_VERSION_2};
+use crate::profile::RecordMesg;
+use crate::schema::{is_protocol_2_base_type, swap_byte_order, Mesg};
+
+const LOCAL_MESG_COUNT: usize = 16;
+// Compressed timestamp headers only have two bits for the local message type.
+const COMPRESSED_LOCAL_MESGS: Range<usize> = 0..4;
+const TIMESTAMP_FIELD_NUM: u8 = 253;
+// Streaming bodies collect this much before writing it out in one call.
+const WRITE_BUFFER_SIZE: usize = 64 * 1024;
+// Unix time of the FIT epoch, 1989-12-31T00:00:00Z.
+const FIT_EPOCH_OFFSET: u64 = 631_065_600;
+
+/// Byte order of the data messages a [`FitWriter`] produces. The file header
+/// and CRC are always little endian.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ByteOrder {
+    #[default]
+    LittleEndian = 0,
+    BigEndian = 1,
+}
+
+/// Where the message writers such as
+/// [`write_file_id_message`](crate::write_file_id_message) take the time
+/// from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Clock {
+    /// The system clock.
+    #[default]
+    System,
+    /// Always this FIT timestamp, in seconds since the FIT epoch, so that
+    /// the same input encodes into a byte-identical file.
+    Fixed(u32),
+}
+
+struct LocalMesg {
+    definition: Vec<u8>,
+    last_used: u64,
+    compressed: bool,
+}
+
+// Tracks which definition is currently bound to each local message type so
+// that data messages can reuse it instead of re-sending the definition.
+#[derive(Default)]
+struct LocalMesgTable {
+    slots: [Option<LocalMesg>; LOCAL_MESG_COUNT],
+    clock: u64,
+}
+
+impl LocalMesgTable {
+    fn lookup(&mut self, mesg_def: &[u8]) -> Option<u8> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.slots.iter_mut().enumerate().find_map(|(local, slot)| match slot {
+            Some(entry) if entry.definition == mesg_def => {
+                entry.last_used = clock;
+                Some(local as u8)
+            }
+            _ => None,
+        })
+    }
+
+    // First free local number in `locals`, otherwise the least recently used
+    // one that `evictable` allows.
+    fn allocate(&self, locals: Range<usize>, evictable: impl Fn(&LocalMesg) -> bool) -> Option<u8> {
+        if let Some(free) = locals.clone().find(|&local| self.slots[local].is_none()) {
+            return Some(free as u8);
+        }
+        locals
+            .filter_map(|local| self.slots[local].as_ref().map(|entry| (local, entry)))
+            .filter(|(_, entry)| evictable(entry))
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(local, _)| local as u8)
+    }
+
+    fn bind(&mut self, local_mesg_number: u8, mesg_def: &[u8], compressed: bool) {
+        self.clock += 1;
+        self.slots[local_mesg_number as usize] = Some(LocalMesg {
+            definition: mesg_def.to_vec(),
+            last_used: self.clock,
+            compressed,
+        });
+    }
+}
+
+// Where the record area goes until the file is finished.
+enum Body<W> {
+    // Straight into the output, whose header gets patched in place.
+    Direct { start: u64, rewrite_header: fn(&mut W, u64, &[u8]) -> io::Result<()> },
+    Memory(Vec<u8>),
+    Spool(Box<dyn Spool>),
+}
+
+/// Scratch storage for [`FitWriter::spooled`]: the record area is written to
+/// it and read back from the start on [`finish`](FitWriter::finish).
+pub trait Spool: Read + Write + Seek {}
+
+impl<S: Read + Write + Seek> Spool for S {}
+
+fn rewrite_header<W: Write + Seek>(inner: &mut W, start: u64, header: &[u8]) -> io::Result<()> {
+    inner.seek(SeekFrom::Start(start))?;
+    inner.write_all(header)?;
+    inner.seek(SeekFrom::End(0))?;
+    Ok(())
+}
+
+/// Encodes a single FIT file into `W`.
+///
+/// The writer owns the running data CRC and the header state, so any number
+/// of files can be produced side by side, into a `File`, a `Cursor<Vec<u8>>`
+/// or anything else that is `Write + Seek`. Sinks that cannot seek, such as
+/// stdout or a socket, go through [`FitWriter::buffered`] or
+/// [`FitWriter::spooled`] instead.
+pub struct FitWriter<W: Write> {
+    inner: W,
+    body: Body<W>,
+    // Record area bytes not yet handed to a Direct or Spool body.
+    pending: Vec<u8>,
+    header_size: HeaderSize,
+    protocol_version: u8,
+    data_size: u32,
+    crc: u16,
+    local_mesgs: LocalMesgTable,
+    compressed_timestamps: bool,
+    last_timestamp: Option<u32>,
+    byte_order: ByteOrder,
+    clock: Clock,
+    // Base types of the developer fields declared so far, by developer data
+    // index and field definition number.
+    developer_base_types: HashMap<(u8, u8), u8>,
+}
+
+impl<W: Write + Seek> FitWriter<W> {
+    /// Writes a placeholder 14 byte file header at the current position of
+    /// `inner`.
+    pub fn new(inner: W) -> Result<Self, FitError> {
+        Self::with_header_size(inner, HeaderSize::default())
+    }
+
+    pub fn with_header_size(mut inner: W, header_size: HeaderSize) -> Result<Self, FitError> {
+        let start = inner.stream_position()?;
+        let body = Body::Direct { start, rewrite_header: rewrite_header::<W> };
+        let mut fit = FitWriter::with_body(inner, body, header_size);
+        let placeholder = fit.file_header().to_bytes();
+        fit.inner.write_all(&placeholder)?;
+        Ok(fit)
+    }
+}
+
+impl<W: Write> FitWriter<W> {
+    /// Holds the record area in memory and writes the whole file to `inner`
+    /// in a single forward pass on [`finish`](Self::finish).
+    pub fn buffered(inner: W) -> Self {
+        FitWriter::with_body(inner, Body::Memory(Vec::new()), HeaderSize::default())
+    }
+
+    /// Like [`buffered`](Self::buffered), but keeps the record area in
+    /// `spool`, e.g. a temporary file, rather than in memory.
+    ///
+    /// The spool is read back on [`finish`](Self::finish), so a file has to
+    /// be opened for reading as well as writing; one from `File::create`
+    /// takes every write and then fails when the file is finished.
+    pub fn spooled(inner: W, spool: impl Spool + 'static) -> Self {
+        FitWriter::with_body(inner, Body::Spool(Box::new(spool)), HeaderSize::default())
+    }
+
+    fn with_body(inner: W, body: Body<W>, header_size: HeaderSize) -> Self {
+        FitWriter {
+            inner,
+            body,
+            pending: Vec::with_capacity(WRITE_BUFFER_SIZE),
+            header_size,
+            protocol_version: PROTOCOL_VERSION_1,
+            data_size: 0,
+            crc: 0,
+            local_mesgs: LocalMesgTable::default(),
+            compressed_timestamps: false,
+            last_timestamp: None,
+            byte_order: ByteOrder::default(),
+            clock: Clock::default(),
+            developer_base_types: HashMap::new(),
+        }
+    }
+
+    /// Opts `RecordMesg` streams into compressed timestamp headers.
+    ///
+    /// A record is written with a compressed header when it is no more than
+    /// 31 s after the previous timestamp and its definition can be bound to
+    /// local message types 0-3. Otherwise it falls back to a normal header
+    /// carrying the full timestamp. Other messages keep to local types 4-15
+    /// while this is enabled.
+    pub fn set_compressed_timestamps(&mut self, enabled: bool) {
+        self.compressed_timestamps = enabled;
+    }
+
+    /// Sets the byte order of the messages written through
+    /// [`write_mesg`](Self::write_mesg) from here on.
+    ///
+    /// Messages are always handed in as little endian; for big endian output
+    /// the writer sets the architecture byte of each definition and reverses
+    /// every multi-byte element, so the result never depends on the host.
+    pub fn set_byte_order(&mut self, byte_order: ByteOrder) {
+        self.byte_order = byte_order;
+    }
+
+    /// Sets the clock the message writers timestamp their messages with.
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    // The current time as a FIT timestamp.
+    pub(crate) fn now(&self) -> u32 {
+        match self.clock {
+            Clock::System => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs());
+                now.saturating_sub(FIT_EPOCH_OFFSET) as u32
+            }
+            Clock::Fixed(timestamp) => timestamp,
+        }
+    }
+
+    // Records the base type of a developer field so that its value can be
+    // byte swapped.
+    pub(crate) fn declare_developer_field(&mut self, developer_data_index: u8, field_definition_number: u8, base_type: u8) {
+        self.developer_base_types.insert((developer_data_index, field_definition_number), base_type);
+    }
+
+    /// Writes a definition message. Any bytes after the native field
+    /// definitions are taken to be the developer field section.
+    ///
+    /// Developer fields and 64-bit integer base types raise the file's
+    /// protocol version to 2.0.
+    pub fn write_message_definition(&mut self, local_mesg_number: u8, mesg_def: &[u8]) -> Result<(), FitError> {
+        if local_mesg_number as usize >= LOCAL_MESG_COUNT {
+            return Err(FitError::InvalidState(format!("local message type {local_mesg_number} is out of range")));
+        }
+        if definition_data_size(mesg_def).is_none() {
+            return Err(FitError::InvalidState("malformed message definition".to_string()));
+        }
+        let mut header = local_mesg_number | 0x40;
+        let fields_end = 5 + mesg_def[4] as usize * 3;
+        if mesg_def.len() > fields_end {
+            header |= 0x20;
+            self.protocol_version = PROTOCOL_VERSION_2;
+        }
+        if mesg_def[5..fields_end].chunks_exact(3).any(|field| is_protocol_2_base_type(field[2])) {
+            self.protocol_version = PROTOCOL_VERSION_2;
+        }
+        self.write_data(&[header])?;
+        self.write_data(mesg_def)?;
+        self.local_mesgs.bind(local_mesg_number, mesg_def, false);
+        Ok(())
+    }
+
+    pub fn write_message(&mut self, local_mesg_number: u8, message: &[u8]) -> Result<(), FitError> {
+        let defined = self.local_mesgs.slots.get(local_mesg_number as usize).is_some_and(Option::is_some);
+        if !defined {
+            return Err(FitError::InvalidState(format!("local message type {local_mesg_number} has no definition")));
+        }
+        self.write_data(&[local_mesg_number])?;
+        self.write_data(message)
+    }
+
+    /// Writes a data message on whichever local message type already holds
+    /// `mesg_def`. If none does, the definition is written first to a free
+    /// local type, evicting the least recently used one when all 16 are taken.
+    pub fn write_mesg(&mut self, mesg_def: &[u8], message: &[u8]) -> Result<(), FitError> {
+        if definition_data_size(mesg_def) != Some(message.len()) {
+            return Err(FitError::InvalidState("message data does not match its definition".to_string()));
+        }
+        self.check_developer_fields(mesg_def)?;
+
+        let mesg_num = u16::from_le_bytes([mesg_def[2], mesg_def[3]]);
+        let timestamp = timestamp_field(mesg_def).map(|(offset, _)| {
+            u32::from_le_bytes(message[offset..offset + 4].try_into().unwrap())
+        });
+
+        let big_endian;
+        let (mesg_def, message) = match self.byte_order {
+            ByteOrder::LittleEndian => (mesg_def, message),
+            ByteOrder::BigEndian => {
+                big_endian = self.to_big_endian(mesg_def, message);
+                (&big_endian.0[..], &big_endian.1[..])
+            }
+        };
+
+        if self.compressed_timestamps && mesg_num == RecordMesg::MESG_NUM {
+            if let Some(timestamp) = timestamp {
+                if self.write_compressed_timestamp_mesg(mesg_def, message, timestamp)? {
+                    return Ok(());
+                }
+            }
+        }
+
+        let local_mesg_number = match self.local_mesgs.lookup(mesg_def) {
+            Some(local_mesg_number) => local_mesg_number,
+            None => {
+                let locals = if self.compressed_timestamps {
+                    COMPRESSED_LOCAL_MESGS.end..LOCAL_MESG_COUNT
+                } else {
+                    0..LOCAL_MESG_COUNT
+                };
+                let local_mesg_number = self.local_mesgs.allocate(locals, |_| true).unwrap_or(0);
+                self.write_message_definition(local_mesg_number, mesg_def)?;
+                local_mesg_number
+            }
+        };
+        self.write_message(local_mesg_number, message)?;
+
+        if timestamp.is_some() {
+            self.last_timestamp = timestamp;
+        }
+        Ok(())
+    }
+
+    // Returns false, without writing anything, when the message has to go out
+    // with a normal header instead.
+    fn write_compressed_timestamp_mesg(&mut self, mesg_def: &[u8], message: &[u8], timestamp: u32) -> Result<bool, FitError> {
+        let time_offset = match self.last_timestamp {
+            Some(last_timestamp) if (0..=31).contains(&timestamp.wrapping_sub(last_timestamp)) => timestamp & 0x1F,
+            _ => return Ok(false),
+        };
+
+        // The timestamp travels in the header, so drop it from the definition.
+        let (offset, index) = timestamp_field(mesg_def).unwrap();
+        let mut compressed_def = mesg_def.to_vec();
+        compressed_def.drain(5 + index * 3..5 + index * 3 + 3);
+        compressed_def[4] -= 1;
+        let mut compressed_message = message.to_vec();
+        compressed_message.drain(offset..offset + 4);
+
+        let local_mesg_number = match self.local_mesgs.lookup(&compressed_def) {
+            Some(local_mesg_number) if COMPRESSED_LOCAL_MESGS.contains(&(local_mesg_number as usize)) => local_mesg_number,
+            Some(_) => return Ok(false),
+            None => match self.local_mesgs.allocate(COMPRESSED_LOCAL_MESGS, |entry| entry.compressed) {
+                Some(local_mesg_number) => {
+                    self.write_message_definition(local_mesg_number, &compressed_def)?;
+                    self.local_mesgs.bind(local_mesg_number, &compressed_def, true);
+                    local_mesg_number
+                }
+                None => return Ok(false),
+            },
+        };
+
+        self.write_data(&[0x80 | (local_mesg_number << 5) | time_offset as u8])?;
+        self.write_data(&compressed_message)?;
+        self.last_timestamp = Some(timestamp);
+        Ok(true)
+    }
+
+    fn check_developer_fields(&self, mesg_def: &[u8]) -> Result<(), FitError> {
+        let fields_end = 5 + mesg_def[4] as usize * 3;
+        for field in mesg_def[fields_end..].get(1..).unwrap_or_default().chunks_exact(3) {
+            if !self.developer_base_types.contains_key(&(field[2], field[0])) {
+                return Err(FitError::InvalidState(format!(
+                    "developer field {} of developer data index {} has no field description",
+                    field[0], field[2],
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn to_big_endian(&self, mesg_def: &[u8], message: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut mesg_def = mesg_def.to_vec();
+        let mut message = message.to_vec();
+        mesg_def[1] = ByteOrder::BigEndian as u8;
+        // The global message number follows the architecture byte too.
+        mesg_def.swap(2, 3);
+
+        let num_fields = mesg_def[4] as usize;
+        let fields_end = 5 + num_fields * 3;
+        let native_fields = mesg_def[5..fields_end].chunks_exact(3).map(|field| (field[1], field[2]));
+        let developer_fields = mesg_def[fields_end..].get(1..).unwrap_or_default().chunks_exact(3).map(|field| {
+            let base_type = self.developer_base_types.get(&(field[2], field[0])).copied().unwrap_or_default();
+            (field[1], base_type)
+        });
+
+        let mut offset = 0;
+        for (size, base_type) in native_fields.chain(developer_fields) {
+            let size = size as usize;
+            swap_byte_order(&mut message[offset..offset + size], base_type);
+            offset += size;
+        }
+        (mesg_def, message)
+    }
+
+    // Copies messages that were encoded elsewhere, e.g. salvaged from a
+    // damaged file, into the record area. They may bind any local message
+    // type, so the writer forgets its own bindings and re-sends definitions.
+    pub(crate) fn write_records(&mut self, records: &[u8], protocol_version: u8) -> Result<(), FitError> {
+        self.protocol_version = self.protocol_version.max(protocol_version);
+        self.local_mesgs = LocalMesgTable::default();
+        self.last_timestamp = None;
+        self.write_data(records)
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<(), FitError> {
+        // The header records the size of the record area in 32 bits.
+        let data_size = u32::try_from(data.len())
+            .ok()
+            .and_then(|len| self.data_size.checked_add(len))
+            .ok_or_else(|| FitError::InvalidState("record area exceeds 4 GiB".to_string()))?;
+        match &mut self.body {
+            Body::Memory(buffer) => buffer.extend_from_slice(data),
+            _ => {
+                self.pending.extend_from_slice(data);
+                if self.pending.len() >= WRITE_BUFFER_SIZE {
+                    self.flush_pending()?;
+                }
+            }
+        }
+        self.data_size = data_size;
+        self.crc = crc_update16(self.crc, data);
+        Ok(())
+    }
+
+    fn flush_pending(&mut self) -> Result<(), FitError> {
+        match &mut self.body {
+            Body::Direct { .. } => self.inner.write_all(&self.pending)?,
+            Body::Spool(spool) => spool.write_all(&self.pending)?,
+            Body::Memory(_) => {}
+        }
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Appends the file CRC, rewrites the header with the final data size and
+    /// hands back the underlying writer.
+    pub fn finish(mut self) -> Result<W, FitError> {
+        let header = self.file_header().to_bytes();
+        // The file CRC covers the header too, which is only known now.
+        let crc = crc_combine16(crc_calc16(&header), self.crc, self.data_size as u64);
+        self.flush_pending()?;
+
+        match &mut self.body {
+            Body::Direct { start, rewrite_header } => {
+                self.inner.write_all(&crc.to_le_bytes())?;
+                rewrite_header(&mut self.inner, *start, &header)?;
+            }
+            Body::Memory(buffer) => {
+                self.inner.write_all(&header)?;
+                self.inner.write_all(buffer)?;
+                self.inner.write_all(&crc.to_le_bytes())?;
+            }
+            Body::Spool(spool) => {
+                spool.flush()?;
+                spool.rewind()?;
+                self.inner.write_all(&header)?;
+                io::copy(spool, &mut self.inner)?;
+                self.inner.write_all(&crc.to_le_bytes())?;
+            }
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn file_header(&self) -> FileHeader {
+        FileHeader {
+            header_size: self.header_size,
+            protocol_version: self.protocol_version,
+            profile_version: PROFILE_VERSION,
+            data_size: self.data_size,
+        }
+    }
+}
+
+// Data offset and field index of the timestamp field in a definition, if any.
+fn timestamp_field(mesg_def: &[u8]) -> Option<(usize, usize)> {
+    let mut offset = 0;
+    let num_fields = mesg_def[4] as usize;
+    for (index, field) in mesg_def[5..5 + num_fields * 3].chunks_exact(3).enumerate() {
+        if field[0] == TIMESTAMP_FIELD_NUM && field[1] == 4 {
+            return Some((offset, index));
+        }
+        offset += field[1] as usize;
+    }
+    None
+}
+
+// Size of the data message that `mesg_def` describes, or None if the
+// definition is malformed.
+fn definition_data_size(mesg_def: &[u8]) -> Option<usize> {
+    let fields_end = 5 + *mesg_def.get(4)? as usize * 3;
+    let definition_end = match mesg_def.get(fields_end) {
+        Some(&num_developer_fields) => fields_end + 1 + num_developer_fields as usize * 3,
+        None => fields_end,
+    };
+    if mesg_def.len() != definition_end {
+        return None;
+    }
+    let native_fields = mesg_def[5..fields_end].chunks_exact(3);
+    let developer_fields = mesg_def[fields_end..].get(1..).unwrap_or_default().chunks_exact(3);
+    Some(native_fields.chain(developer_fields).map(|field| field[1] as usize).sum())
+}
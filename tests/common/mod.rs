@@ -0,0 +1,39 @@
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code)]
+
+use echo2garmin::{FitError, FitWriter};
+
+pub const PROTOCOL_VERSION_1: u8 = 0x10;
+pub const PROTOCOL_VERSION_2: u8 = 0x20;
+
+// Same CRC as the FIT SDK, computed bit by bit so the tests do not lean on
+// the table the crate itself uses.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &byte in data {
+        crc ^= byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xA001 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+// A 14 byte header with a zero header CRC, the records and a correct file CRC.
+pub fn fit_file(protocol_version: u8, records: &[u8]) -> Vec<u8> {
+    let mut data = vec![14, protocol_version, 0xA6, 0x52];
+    data.extend_from_slice(&(records.len() as u32).to_le_bytes());
+    data.extend_from_slice(b".FIT\0\0");
+    data.extend_from_slice(records);
+    let crc = crc16(&data);
+    data.extend_from_slice(&crc.to_le_bytes());
+    data
+}
+
+// Runs `write` against a buffered writer and returns the finished file.
+pub fn encode(write: impl FnOnce(&mut FitWriter<Vec<u8>>) -> Result<(), FitError>) -> Vec<u8> {
+    let mut fit = FitWriter::buffered(Vec::new());
+    write(&mut fit).unwrap();
+    fit.finish().unwrap()
+}
@@ -0,0 +1,107 @@
+//! Component fields: compressed speed/distance packing and accumulated
+//! totals, checked against the widths and scales in the profile.
+
+use echo2garmin::schema::FIT_UINT16_INVALID;
+use echo2garmin::{compress_speed_distance, decode, write_mesg, Accumulator, Component, FitWriter, Mesg, RecordMesg};
+
+// Splits packed compressed_speed_distance bytes into (speed, distance).
+fn unpack(bytes: [u8; 3]) -> (u32, u32) {
+    let packed = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]);
+    (packed & 0xFFF, packed >> 12)
+}
+
+#[test]
+fn profile_components() {
+    assert_eq!(
+        RecordMesg::COMPRESSED_SPEED_DISTANCE_COMPONENTS,
+        [
+            Component { field_num: 6, name: "speed", scale: 100.0, offset: 0.0, units: "m/s", bits: 12, accumulate: false },
+            Component { field_num: 5, name: "distance", scale: 16.0, offset: 0.0, units: "m", bits: 12, accumulate: true },
+        ]
+    );
+    assert_eq!(
+        RecordMesg::COMPRESSED_ACCUMULATED_POWER_COMPONENTS,
+        [Component { field_num: 29, name: "accumulated_power", scale: 1.0, offset: 0.0, units: "watts", bits: 16, accumulate: true }]
+    );
+    // A field with a single component keeps the component's scale.
+    assert_eq!(RecordMesg::ALTITUDE_COMPONENTS[0].offset, 500.0);
+    let field = RecordMesg::FIELDS.iter().find(|field| field.name == "compressed_speed_distance").unwrap();
+    assert_eq!((field.scale, field.offset, field.units), (1.0, 0.0, ""));
+}
+
+#[test]
+fn speed_distance() {
+    assert_eq!(unpack(compress_speed_distance(5.0, 100.0)), (500, 1600));
+    assert_eq!(compress_speed_distance(5.0, 100.0), [0xF4, 0x01, 0x64]);
+    assert_eq!(unpack(compress_speed_distance(-1.0, -1.0)), (0, 0));
+
+    // Distance rolls over every 256 m.
+    assert_eq!(unpack(compress_speed_distance(0.0, 256.0)), (0, 0));
+    assert_eq!(compress_speed_distance(3.0, 300.0), compress_speed_distance(3.0, 44.0));
+
+    // Speed saturates one below all ones, which would read as invalid, so
+    // the packed bytes never land on [0xFF; 3] either.
+    assert_eq!(unpack(compress_speed_distance(40.94, 0.0)), (4094, 0));
+    assert_eq!(unpack(compress_speed_distance(1000.0, 0.0)), (4094, 0));
+    assert_eq!(compress_speed_distance(f64::MAX, 255.9375), [0xFE, 0xFF, 0xFF]);
+}
+
+#[test]
+fn accumulator() {
+    let mut total = Accumulator::default();
+    for value in [40_000, 30_000, 500] {
+        total.add(value);
+    }
+    assert_eq!(total.total(), 70_500);
+    assert_eq!(total.rolled(16), 70_500 - 65_536);
+    assert_eq!(total.rolled(8), 70_500 % 256);
+    assert_eq!(total.rolled(1), 0);
+    assert_eq!(total.rolled(64), 70_500);
+
+    total.add(u64::MAX);
+    assert_eq!(total.total(), 70_499);
+}
+
+#[test]
+#[should_panic]
+fn rolled_zero_bits() {
+    Accumulator::default().rolled(0);
+}
+
+#[test]
+#[should_panic]
+fn rolled_too_many_bits() {
+    Accumulator::default().rolled(65);
+}
+
+#[test]
+fn compressed_power() {
+    let mut total = Accumulator::default();
+    total.add(70_000);
+    let mut record = RecordMesg { timestamp: 1_000_000_000, ..Default::default() };
+    record.set_compressed_power(&total);
+    assert_eq!(record.compressed_accumulated_power, 4464);
+
+    // A total that rolls over to the invalid value leaves the field out.
+    let mut total = Accumulator::default();
+    total.add(0xFFFF);
+    record.set_compressed_power(&total);
+    assert_eq!(record.compressed_accumulated_power, FIT_UINT16_INVALID);
+    let mut fit = FitWriter::buffered(Vec::new());
+    write_mesg(&mut fit, &record, &[]).unwrap();
+    let file = decode(&fit.finish().unwrap()).unwrap();
+    let mesg = file.data_mesgs().next().unwrap();
+    assert!(mesg.fields.iter().all(|field| field.num != 28));
+}
+
+#[test]
+fn compressed_speed_distance_round_trip() {
+    let mut record = RecordMesg { timestamp: 1_000_000_000, ..Default::default() };
+    record.set_compressed_speed_distance(8.25, 1234.5);
+    let mut fit = FitWriter::buffered(Vec::new());
+    write_mesg(&mut fit, &record, &[]).unwrap();
+    let file = decode(&fit.finish().unwrap()).unwrap();
+    let decoded: RecordMesg = file.data_mesgs().next().unwrap().decode().unwrap();
+    assert_eq!(decoded, record);
+    assert_eq!(unpack(decoded.compressed_speed_distance), (825, (1234.5 * 16.0) as u32 % 4096));
+}
@@ -0,0 +1,111 @@
+//! The text and JSON renderings of `dump`: names, units and scaled values
+//! from the profile and from field descriptions, and messages, fields and
+//! developer fields nobody described shown by number.
+
+use echo2garmin::{
+    dump, write_echo_developer_data, write_file_id_message, write_mesg, DeveloperField, DumpFormat, EchoMetric, FitWriter,
+    RecordMesg,
+};
+
+mod common;
+use common::{encode, fit_file, PROTOCOL_VERSION_2};
+
+fn dumped(data: &[u8], format: DumpFormat) -> String {
+    let mut out = Vec::new();
+    dump(data, format, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn assert_contains(dumped: &str, expected: &[&str]) {
+    for expected in expected {
+        assert!(dumped.contains(expected), "{expected:?} missing from:\n{dumped}");
+    }
+}
+
+// A record with scaled fields and Echo developer fields, a manufacturer
+// specific message the profile does not know, and a record field it does
+// not know either.
+fn activity() -> Vec<u8> {
+    encode(|fit: &mut FitWriter<Vec<u8>>| {
+        write_file_id_message(fit)?;
+        write_echo_developer_data(fit)?;
+        let record = RecordMesg { timestamp: 1_000_000_000, heart_rate: 150, altitude: 2600, speed: 8250, ..Default::default() };
+        let developer_fields =
+            [DeveloperField::try_from(EchoMetric::CaloriesPerMinute(12.5))?, DeveloperField::try_from(EchoMetric::FanRpm(900))?];
+        write_mesg(fit, &record, &developer_fields)?;
+        // mesg 0xFF00: field 0 uint16, field 1 uint8.
+        fit.write_mesg(&[0, 0, 0x00, 0xFF, 2, 0, 2, 0x84, 1, 1, 0x02], &[0x34, 0x12, 7])?;
+        // record: timestamp, field 200 uint8.
+        fit.write_mesg(&[0, 0, 20, 0, 2, 253, 4, 0x86, 200, 1, 0x02], &[0x01, 0xCA, 0x9A, 0x3B, 42])
+    })
+}
+
+// A record carrying developer field 5 of developer data index 3, which no
+// field description declares.
+fn undescribed_developer_field() -> Vec<u8> {
+    let records = [
+        0x60, 0, 0, 20, 0, 1, 253, 4, 0x86, 1, 5, 2, 3, // definition: record, timestamp, developer 3.5
+        0x00, 0x00, 0xCA, 0x9A, 0x3B, 0x34, 0x12, // data
+    ];
+    fit_file(PROTOCOL_VERSION_2, &records)
+}
+
+#[test]
+fn text() {
+    let text = dumped(&activity(), DumpFormat::Text);
+    assert_contains(
+        &text,
+        &[
+            "header: 14 bytes, protocol 2.0, profile 21.158,",
+            ": record (20), little endian
+    253 timestamp: uint32, 4 bytes
+    2 altitude: uint16, 2 bytes
+    3 heart_rate: uint8, 1 bytes
+    6 speed: uint16, 2 bytes
+    developer 0.1: 2 bytes
+    developer 0.3: 2 bytes
+",
+            ": record (20)
+    timestamp: 2021-09-08T01:46:40Z (1000000000)
+    altitude: 20 m
+    heart_rate: 150 bpm
+    speed: 8.25 m/s
+    calories_per_minute: 12.5 kcal/min
+    fan_rpm: 900 rpm
+",
+            "    field_name: \"calories_per_minute\"\n",
+            ": mesg_65280 (65280), little endian\n    0 field_0: uint16, 2 bytes\n    1 field_1: uint8, 1 bytes\n",
+            ": mesg_65280 (65280)\n    field_0: 4660\n    field_1: 7\n",
+            "    timestamp: 2021-09-08T01:46:41Z (1000000001)\n    field_200: 42\n",
+        ],
+    );
+    assert!(text.ends_with("file CRC ok\n"), "{text}");
+
+    let text = dumped(&undescribed_developer_field(), DumpFormat::Text);
+    assert_contains(&text, &["    developer 3.5: 2 bytes\n", "    developer_3_5: 3412\n"]);
+}
+
+#[test]
+fn json() {
+    let json = dumped(&activity(), DumpFormat::Json);
+    assert_contains(
+        &json,
+        &[
+            "{\"header\":{\"header_size\":14,\"protocol_version\":\"2.0\",\"profile_version\":\"21.158\",",
+            "\"developer_fields\":[{\"developer_data_index\":0,\"num\":1,\"size\":2},{\"developer_data_index\":0,\"num\":3,\"size\":2}]",
+            "\"timestamp\":{\"value\":1000000000,\"units\":\"s\"}",
+            "\"altitude\":{\"value\":20,\"units\":\"m\"}",
+            "\"heart_rate\":{\"value\":150,\"units\":\"bpm\"}",
+            "\"speed\":{\"value\":8.25,\"units\":\"m/s\"}",
+            "\"calories_per_minute\":{\"value\":12.5,\"units\":\"kcal/min\"}",
+            "\"fan_rpm\":{\"value\":900,\"units\":\"rpm\"}",
+            "\"name\":\"mesg_65280\",\"architecture\":\"little_endian\",\"fields\":[{\"num\":0,\"name\":\"field_0\",\"base_type\":\"uint16\",\"size\":2},",
+            "\"name\":\"mesg_65280\",\"fields\":{\"field_0\":{\"value\":4660},\"field_1\":{\"value\":7}}}",
+            "\"field_200\":{\"value\":42}",
+        ],
+    );
+    assert!(json.ends_with("\n],\"error\":null}\n"), "{json}");
+
+    let json = dumped(&undescribed_developer_field(), DumpFormat::Json);
+    assert_contains(&json, &["\"developer_3_5\":{\"value\":[52,18]}"]);
+}
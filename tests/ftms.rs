@@ -0,0 +1,103 @@
+//! Indoor Bike Data notifications, laid out by hand from the Fitness Machine
+//! Service specification, and the records they turn into.
+
+use echo2garmin::{decode, write_mesg, FitWriter, FtmsError, IndoorBikeData, IndoorBikeSampler, RecordMesg};
+
+// Every flag from average speed to remaining time set, so only the
+// instantaneous speed is left out.
+const ALL_BUT_SPEED: [u8; 28] = [
+    0xFF, 0x1F, // flags
+    0xD0, 0x07, // average speed, 20.00 km/h
+    0xA0, 0x00, // instantaneous cadence, 80.0 rpm
+    0x91, 0x00, // average cadence, 72.5 rpm
+    0x40, 0xE2, 0x01, // total distance, 123456 m
+    0xFB, 0xFF, // resistance level, -5
+    0xFA, 0x00, // instantaneous power, 250 W
+    0x1E, 0xFF, // average power, -226 W
+    0x2C, 0x01, 0x58, 0x02, 0x0A, // expended energy, 300 kcal, 600 kcal/h, 10 kcal/min
+    0x96, // heart rate, 150 bpm
+    0x2D, // metabolic equivalent, 4.5
+    0x10, 0x0E, // elapsed time, 3600 s
+    0x2C, 0x01, // remaining time, 300 s
+];
+
+#[test]
+fn every_field() {
+    let data = IndoorBikeData::parse(&ALL_BUT_SPEED).unwrap();
+    assert_eq!(
+        data,
+        IndoorBikeData {
+            more_data: true,
+            instantaneous_speed: None,
+            average_speed: Some(20.0),
+            instantaneous_cadence: Some(80.0),
+            average_cadence: Some(72.5),
+            total_distance: Some(123_456),
+            resistance_level: Some(-5),
+            instantaneous_power: Some(250),
+            average_power: Some(-226),
+            total_energy: Some(300),
+            energy_per_hour: Some(600),
+            energy_per_minute: Some(10),
+            heart_rate: Some(150),
+            metabolic_equivalent: Some(4.5),
+            elapsed_time: Some(3600),
+            remaining_time: Some(300),
+        }
+    );
+
+    // Bit 0 clear: the instantaneous speed comes first.
+    let data = IndoorBikeData::parse(&[0x00, 0x00, 0x1A, 0x0A]).unwrap();
+    assert_eq!(data, IndoorBikeData { instantaneous_speed: Some(25.86), ..Default::default() });
+}
+
+#[test]
+fn energy_not_available() {
+    let data = IndoorBikeData::parse(&[0x00, 0x01, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]).unwrap();
+    assert_eq!((data.total_energy, data.energy_per_hour, data.energy_per_minute), (None, None, None));
+}
+
+#[test]
+fn truncated() {
+    assert_eq!(IndoorBikeData::parse(&[0x44]), Err(FtmsError::Truncated { len: 1, expected: 2 }));
+    assert_eq!(IndoorBikeData::parse(&[0x44, 0x00, 0x1A, 0x0A, 0xA0]), Err(FtmsError::Truncated { len: 5, expected: 8 }));
+    for len in 0..ALL_BUT_SPEED.len() {
+        assert!(IndoorBikeData::parse(&ALL_BUT_SPEED[..len]).is_err());
+    }
+    // Fields from a later version of the service are skipped.
+    assert!(IndoorBikeData::parse(&[0x00, 0x20, 0x1A, 0x0A, 0x01, 0x02]).is_ok());
+}
+
+#[test]
+fn samples_become_records() {
+    let mut sampler = IndoorBikeSampler::new();
+    // The first notification has More Data set and is held back.
+    assert_eq!(sampler.push(&ALL_BUT_SPEED, 1_000_000).unwrap(), None);
+    let record = sampler.push(&[0x00, 0x00, 0x10, 0x0E], 1_000_001).unwrap().unwrap();
+    assert_eq!(
+        record,
+        RecordMesg {
+            timestamp: 1_000_001,
+            heart_rate: 150,
+            cadence: 80,
+            distance: 12_345_600,
+            speed: 10_000,
+            power: 250,
+            calories: 300,
+            ..Default::default()
+        }
+    );
+
+    // Later samples follow the machine's elapsed time, not arrival time.
+    let record = sampler.push(&[0x00, 0x08, 0x10, 0x0E, 0x12, 0x0E], 1_000_005).unwrap().unwrap();
+    assert_eq!(record.timestamp, 1_000_003);
+    assert_eq!(record.speed, 10_000);
+    // Without an elapsed time, the arrival time is used.
+    let record = sampler.push(&[0x00, 0x00, 0x10, 0x0E], 1_000_006).unwrap().unwrap();
+    assert_eq!(record.timestamp, 1_000_006);
+
+    let mut fit = FitWriter::buffered(Vec::new());
+    write_mesg(&mut fit, &record, &[]).unwrap();
+    let file = decode(&fit.finish().unwrap()).unwrap();
+    assert_eq!(file.data_mesgs().next().unwrap().decode::<RecordMesg>(), Some(record));
+}
@@ -0,0 +1,87 @@
+//! Local message type allocation: definitions are reused while they are
+//! bound, and the least recently used one gives way once all 16 are taken.
+
+use echo2garmin::{decode, FitWriter, Record};
+
+// Manufacturer specific message numbers, so every layout is distinct.
+const FIRST_MESG_NUM: u16 = 0xFF00;
+
+// A little-endian definition of `mesg_num` with a single uint8 field.
+fn definition(mesg_num: u16) -> Vec<u8> {
+    let [low, high] = mesg_num.to_le_bytes();
+    vec![0, 0, low, high, 1, 0, 1, 0x02]
+}
+
+// (local, mesg_num) of each definition, (local, mesg_num, value) of each
+// data message.
+type Definitions = Vec<(u8, u16)>;
+type DataMesgs = Vec<(u8, u16, u8)>;
+
+// Writes one message per (layout, value) pair and decodes the file again.
+fn write(mesgs: &[(u16, u8)]) -> (Definitions, DataMesgs) {
+    let mut fit = FitWriter::buffered(Vec::new());
+    for &(layout, value) in mesgs {
+        fit.write_mesg(&definition(FIRST_MESG_NUM + layout), &[value]).unwrap();
+    }
+    let file = decode(&fit.finish().unwrap()).unwrap();
+
+    let mut definitions = Vec::new();
+    let mut data = Vec::new();
+    for record in &file.records {
+        match record {
+            Record::Definition(definition) => definitions.push((definition.local_mesg_number, definition.mesg_num)),
+            Record::Data(mesg) => data.push((mesg.local_mesg_number, mesg.mesg_num, mesg.fields[0].value[0])),
+        }
+    }
+    (definitions, data)
+}
+
+#[test]
+fn definitions_are_reused() {
+    let (definitions, data) = write(&[(0, 1), (0, 2), (1, 3), (0, 4), (1, 5)]);
+    assert_eq!(definitions, [(0, FIRST_MESG_NUM), (1, FIRST_MESG_NUM + 1)]);
+    assert_eq!(
+        data,
+        [(0, FIRST_MESG_NUM, 1), (0, FIRST_MESG_NUM, 2), (1, FIRST_MESG_NUM + 1, 3), (0, FIRST_MESG_NUM, 4), (1, FIRST_MESG_NUM + 1, 5)]
+    );
+}
+
+#[test]
+fn least_recently_used_is_evicted() {
+    // Fill all 16 local types, then use layout 0 again so that layout 1 is
+    // the least recently used when a 17th layout comes along.
+    let mut mesgs: Vec<(u16, u8)> = (0..16).map(|layout| (layout, layout as u8)).collect();
+    mesgs.push((0, 100));
+    mesgs.push((16, 116));
+    let (definitions, data) = write(&mesgs);
+
+    let expected: Vec<(u8, u16)> = (0..16).map(|local| (local, FIRST_MESG_NUM + local as u16)).collect();
+    assert_eq!(definitions[..16], expected);
+    assert_eq!(definitions[16..], [(1, FIRST_MESG_NUM + 16)]);
+    assert_eq!(data[16..], [(0, FIRST_MESG_NUM, 100), (1, FIRST_MESG_NUM + 16, 116)]);
+}
+
+#[test]
+fn evicted_layout_is_written_again() {
+    // Layout 1 is evicted by layout 16, then comes back and has to be
+    // defined anew, this time in place of layout 2.
+    let mut mesgs: Vec<(u16, u8)> = (0..16).map(|layout| (layout, layout as u8)).collect();
+    mesgs.extend([(0, 100), (16, 116), (1, 101), (16, 117), (1, 102)]);
+    let (definitions, data) = write(&mesgs);
+
+    assert_eq!(definitions[16..], [(1, FIRST_MESG_NUM + 16), (2, FIRST_MESG_NUM + 1)]);
+    assert_eq!(
+        data[16..],
+        [
+            (0, FIRST_MESG_NUM, 100),
+            (1, FIRST_MESG_NUM + 16, 116),
+            (2, FIRST_MESG_NUM + 1, 101),
+            (1, FIRST_MESG_NUM + 16, 117),
+            (2, FIRST_MESG_NUM + 1, 102),
+        ]
+    );
+    // Every message still decodes to the layout and value it was written with.
+    let written: Vec<(u16, u8)> = mesgs.iter().map(|&(layout, value)| (FIRST_MESG_NUM + layout, value)).collect();
+    let decoded: Vec<(u16, u8)> = data.iter().map(|&(_, mesg_num, value)| (mesg_num, value)).collect();
+    assert_eq!(decoded, written);
+}
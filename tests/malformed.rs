@@ -0,0 +1,120 @@
+//! Malformed and hostile inputs that once crashed, or could crash, the
+//! decoder. Every entry point must return an error or a report for these,
+//! never panic.
+
+use echo2garmin::{decode, dump, repair, validate, DecodeError, DumpFormat, IssueKind};
+
+mod common;
+use common::PROTOCOL_VERSION_2;
+
+// A file of `records` with a correct file CRC.
+fn fit_file(records: &[u8]) -> Vec<u8> {
+    common::fit_file(PROTOCOL_VERSION_2, records)
+}
+
+// Runs every reader over `data`; each must come back without panicking.
+fn read_all(data: &[u8]) -> Result<(), DecodeError> {
+    let _ = validate(data);
+    let _ = dump(data, DumpFormat::Text, &mut std::io::sink());
+    let _ = dump(data, DumpFormat::Json, &mut std::io::sink());
+    let _ = repair(data);
+    decode(data).map(drop)
+}
+
+#[test]
+fn compressed_timestamp_rolls_over_at_u32_max() {
+    // A record timestamped 0xFFFFFFFE, then a compressed header with a time
+    // offset of 1, which wraps round to 1.
+    let records = [
+        0x40, 0, 0, 20, 0, 1, 253, 4, 0x86, // definition: record, timestamp
+        0x00, 0xFE, 0xFF, 0xFF, 0xFF, // data, full timestamp
+        0x41, 0, 0, 20, 0, 1, 7, 2, 0x84, // definition: record, power
+        0xA1, 0xC8, 0x00, // compressed header, local 1, offset 1
+    ];
+    let data = fit_file(&records);
+    read_all(&data).unwrap();
+    let file = decode(&data).unwrap();
+    assert_eq!(file.data_mesgs().last().unwrap().compressed_timestamp, Some(1));
+}
+
+#[test]
+fn oversized_field_counts() {
+    // 255 native and 255 developer fields of 255 bytes each, far more data
+    // than the file holds.
+    let mut records = vec![0x60, 0, 0, 20, 0, 255];
+    for num in 0..255u8 {
+        records.extend_from_slice(&[num, 255, 0x0D]);
+    }
+    records.push(255);
+    for num in 0..255u8 {
+        records.extend_from_slice(&[num, 255, 0]);
+    }
+    records.extend_from_slice(&[0x00, 1, 2, 3]);
+    let data = fit_file(&records);
+    assert!(matches!(read_all(&data), Err(DecodeError::UnexpectedEof { .. })));
+}
+
+#[test]
+fn definition_cut_short() {
+    let data = fit_file(&[0x40, 0, 0, 20, 0, 200, 253, 4]);
+    assert!(matches!(read_all(&data), Err(DecodeError::UnexpectedEof { .. })));
+}
+
+#[test]
+fn developer_fields_without_description() {
+    // Big-endian developer field whose base type was never described, so
+    // it cannot be byte swapped; the raw bytes are kept.
+    let records = [
+        0x60, 0, 1, 0, 20, 1, 253, 4, 0x86, 1, 9, 3, 7, // definition with one developer field
+        0x00, 0x3B, 0x9A, 0xCA, 0x00, 0x12, 0x34, 0x56, // data
+    ];
+    let data = fit_file(&records);
+    read_all(&data).unwrap();
+    let file = decode(&data).unwrap();
+    let mesg = file.data_mesgs().next().unwrap();
+    assert_eq!(mesg.developer_fields[0].value, [0x12, 0x34, 0x56]);
+}
+
+#[test]
+fn undefined_local_mesg() {
+    for header in [0x05, 0xE0] {
+        let data = fit_file(&[0x40, 0, 0, 20, 0, 1, 253, 4, 0x86, 0x00, 0, 0, 0, 0x10, header, 0, 0, 0, 0]);
+        assert!(matches!(read_all(&data), Err(DecodeError::UndefinedLocalMesg { .. })));
+    }
+}
+
+#[test]
+fn compressed_timestamp_without_timestamp() {
+    let data = fit_file(&[0x40, 0, 0, 20, 0, 1, 7, 2, 0x84, 0x85, 0xC8, 0x00]);
+    assert!(matches!(read_all(&data), Err(DecodeError::MissingTimestamp { offset: 23 })));
+}
+
+#[test]
+fn unknown_architecture() {
+    let data = fit_file(&[0x40, 0, 7, 20, 0, 0]);
+    assert!(matches!(read_all(&data), Err(DecodeError::MalformedDefinition { offset: 14 })));
+}
+
+#[test]
+fn field_sizes_that_do_not_fit_their_base_type() {
+    // A 3 byte uint16 and a zero length uint32: decodable, but flagged.
+    let data = fit_file(&[0x40, 0, 0, 20, 0, 2, 7, 3, 0x84, 5, 0, 0x86, 0x00, 1, 2, 3]);
+    read_all(&data).unwrap();
+    let kinds: Vec<IssueKind> = validate(&data).into_iter().map(|issue| issue.kind).collect();
+    assert!(kinds.contains(&IssueKind::FieldSizeMismatch { mesg_num: 20, field_num: 7, size: 3, base_type: 0x84 }));
+    assert!(kinds.contains(&IssueKind::FieldSizeMismatch { mesg_num: 20, field_num: 5, size: 0, base_type: 0x86 }));
+}
+
+#[test]
+fn data_size_past_end_of_file() {
+    let mut data = fit_file(&[0x40, 0, 0, 20, 0, 0, 0x00]);
+    data[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(matches!(read_all(&data), Err(DecodeError::UnexpectedEof { .. })));
+}
+
+#[test]
+fn headers() {
+    for data in [&b""[..], &[14], &[12, 0x10, 0, 0, 0, 0, 0, 0, b'.', b'F', b'I'], &[13; 20], b"\x0e\x10\0\0\0\0\0\0.fit\0\0"] {
+        assert_eq!(read_all(data), Err(DecodeError::InvalidHeader));
+    }
+}
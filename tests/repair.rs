@@ -0,0 +1,173 @@
+//! Repairing recordings that were cut short: what is salvaged, what is
+//! dropped, and which summaries are added to close the file.
+
+use echo2garmin::profile::{ActivityMesg, Event, EventMesg, EventType, FileIdMesg, LapMesg, SessionMesg};
+use echo2garmin::{decode, repair, validate, write_mesg, FitError, FitWriter, Mesg, RecordMesg};
+
+mod common;
+use common::encode;
+
+const START: u32 = 1_000_000_000;
+
+// file_id and a timer start.
+fn start(fit: &mut FitWriter<Vec<u8>>) -> Result<(), FitError> {
+    write_mesg(fit, &FileIdMesg { product: 1, ..Default::default() }, &[])?;
+    write_mesg(fit, &EventMesg { timestamp: START, event: Event::Timer.into(), event_type: EventType::Start.into(), ..Default::default() }, &[])
+}
+
+// One record a second for `seconds`, starting `from` seconds in, at 8 m/s.
+fn records(fit: &mut FitWriter<Vec<u8>>, from: u32, seconds: u32) -> Result<(), FitError> {
+    for second in from..from + seconds {
+        let record =
+            RecordMesg { timestamp: START + second, heart_rate: 120 + second as u8, power: 200, distance: second * 800, ..Default::default() };
+        write_mesg(fit, &record, &[])?;
+    }
+    Ok(())
+}
+
+fn lap(fit: &mut FitWriter<Vec<u8>>, message_index: u16, from: u32, to: u32) -> Result<(), FitError> {
+    write_mesg(fit, &LapMesg { message_index, timestamp: START + to, start_time: START + from, ..Default::default() }, &[])
+}
+
+// The timer stop, a lap, session and activity for a ride of `seconds`.
+fn finish(fit: &mut FitWriter<Vec<u8>>, seconds: u32) -> Result<(), FitError> {
+    let end = START + seconds;
+    write_mesg(fit, &EventMesg { timestamp: end, event: Event::Timer.into(), event_type: EventType::Stop.into(), ..Default::default() }, &[])?;
+    lap(fit, 0, 0, seconds)?;
+    write_mesg(fit, &SessionMesg { timestamp: end, start_time: START, num_laps: 1, ..Default::default() }, &[])?;
+    write_mesg(fit, &ActivityMesg { timestamp: end, num_sessions: 1, ..Default::default() }, &[])
+}
+
+fn mesgs<M: Mesg>(data: &[u8]) -> Vec<M> {
+    decode(data).unwrap().data_mesgs().filter_map(|mesg| mesg.decode()).collect()
+}
+
+#[test]
+fn truncated_recording() {
+    let data = encode(|fit| {
+        start(fit)?;
+        records(fit, 0, 10)
+    });
+    // Cut three bytes into the last record, which loses the file CRC too.
+    let record_size = 1 + 4 + 4 + 1 + 2;
+    let cut = data.len() - 2 - record_size + 3;
+    let repaired = repair(&data[..cut]).unwrap();
+    assert_eq!(repaired.dropped, 3);
+    assert_eq!(repaired.synthesized, [LapMesg::MESG_NUM, SessionMesg::MESG_NUM, ActivityMesg::MESG_NUM]);
+    assert_eq!(validate(&repaired.data), []);
+
+    assert_eq!(mesgs::<RecordMesg>(&repaired.data).len(), 9);
+    let lap = &mesgs::<LapMesg>(&repaired.data)[0];
+    assert_eq!((lap.message_index, lap.start_time, lap.timestamp), (0, START, START + 8));
+    assert_eq!((lap.avg_heart_rate, lap.max_heart_rate), (124, 128));
+    assert_eq!(lap.total_distance, 6400);
+    let session = &mesgs::<SessionMesg>(&repaired.data)[0];
+    assert_eq!((session.start_time, session.timestamp, session.num_laps), (START, START + 8, 1));
+    let activity = &mesgs::<ActivityMesg>(&repaired.data)[0];
+    assert_eq!((activity.timestamp, activity.num_sessions), (START + 8, 1));
+    assert_eq!((activity.event, activity.event_type), (Event::Activity.into(), EventType::Stop.into()));
+}
+
+#[test]
+fn stale_data_size() {
+    // The recorder died before it could write the data size or the CRC, so
+    // the header still holds the placeholder written when it opened.
+    let data = encode(|fit| {
+        start(fit)?;
+        records(fit, 0, 10)
+    });
+    let mut stale = data[..data.len() - 2].to_vec();
+    stale[4..8].copy_from_slice(&0u32.to_le_bytes());
+    stale[12..14].copy_from_slice(&[0, 0]);
+
+    let repaired = repair(&stale).unwrap();
+    assert_eq!(repaired.dropped, 0);
+    assert_eq!(repaired.synthesized, [LapMesg::MESG_NUM, SessionMesg::MESG_NUM, ActivityMesg::MESG_NUM]);
+    assert_eq!(validate(&repaired.data), []);
+    assert_eq!(mesgs::<RecordMesg>(&repaired.data).len(), 10);
+}
+
+#[test]
+fn complete_file() {
+    let data = encode(|fit| {
+        start(fit)?;
+        records(fit, 0, 10)?;
+        finish(fit, 10)
+    });
+    let repaired = repair(&data).unwrap();
+    assert_eq!((repaired.dropped, repaired.synthesized.len()), (0, 0));
+    assert_eq!(repaired.data, data);
+
+    // Losing only the CRC leaves every summary in place; none is added
+    // after the activity.
+    let repaired = repair(&data[..data.len() - 1]).unwrap();
+    assert_eq!(repaired.synthesized, []);
+    assert_eq!(validate(&repaired.data), []);
+    assert_eq!(mesgs::<ActivityMesg>(&repaired.data).len(), 1);
+
+    // Nor is a lap added after the session and activity of a file that
+    // never had one.
+    let data = encode(|fit| {
+        start(fit)?;
+        records(fit, 0, 10)?;
+        write_mesg(fit, &SessionMesg { timestamp: START + 9, start_time: START, ..Default::default() }, &[])?;
+        write_mesg(fit, &ActivityMesg { timestamp: START + 9, num_sessions: 1, ..Default::default() }, &[])
+    });
+    let repaired = repair(&data[..data.len() - 1]).unwrap();
+    assert_eq!(repaired.synthesized, []);
+    assert_eq!(validate(&repaired.data), []);
+}
+
+#[test]
+fn session_without_activity() {
+    let data = encode(|fit| {
+        start(fit)?;
+        records(fit, 0, 10)?;
+        finish(fit, 10)
+    });
+    // Cut inside the activity message.
+    let repaired = repair(&data[..data.len() - 6]).unwrap();
+    assert_eq!(repaired.synthesized, [ActivityMesg::MESG_NUM]);
+    assert_eq!(validate(&repaired.data), []);
+    assert_eq!((mesgs::<LapMesg>(&repaired.data).len(), mesgs::<SessionMesg>(&repaired.data).len()), (1, 1));
+    let activity = &mesgs::<ActivityMesg>(&repaired.data)[0];
+    assert_eq!((activity.timestamp, activity.num_sessions), (START + 10, 1));
+}
+
+#[test]
+fn cut_in_second_lap() {
+    let data = encode(|fit| {
+        start(fit)?;
+        records(fit, 0, 10)?;
+        lap(fit, 0, 0, 9)?;
+        records(fit, 10, 5)
+    });
+    let repaired = repair(&data).unwrap();
+    assert_eq!(repaired.synthesized, [LapMesg::MESG_NUM, SessionMesg::MESG_NUM, ActivityMesg::MESG_NUM]);
+    assert_eq!(validate(&repaired.data), []);
+
+    // The new lap covers only the records after the first one.
+    let laps = mesgs::<LapMesg>(&repaired.data);
+    assert_eq!(laps.len(), 2);
+    assert_eq!((laps[1].message_index, laps[1].start_time, laps[1].timestamp), (1, START + 10, START + 14));
+    // From the end of the first lap at 72 m to 112 m, in centimetres.
+    assert_eq!(laps[1].total_distance, 4000);
+    let session = &mesgs::<SessionMesg>(&repaired.data)[0];
+    assert_eq!((session.start_time, session.timestamp, session.num_laps), (START, START + 14, 2));
+    assert_eq!(session.total_distance, 11200);
+}
+
+#[test]
+fn timestamp_of_another_type() {
+    // An event whose field 253 is four raw bytes, not a uint32 timestamp, so
+    // it must not move the end of the summaries.
+    let data = encode(|fit| {
+        start(fit)?;
+        records(fit, 0, 10)?;
+        fit.write_mesg(&[0, 0, 21, 0, 1, 253, 4, 0x0D], &[0, 0, 0, 0x7F])
+    });
+    let repaired = repair(&data).unwrap();
+    assert_eq!(validate(&repaired.data), []);
+    let session = &mesgs::<SessionMesg>(&repaired.data)[0];
+    assert_eq!(session.timestamp, START + 9);
+}
@@ -0,0 +1,414 @@
+//! Encodes each message type, decodes the file again and checks that every
+//! field survives the trip, in both byte orders.
+
+use std::fmt::Debug;
+use std::fs::{self, OpenOptions};
+use std::io::Cursor;
+use std::{env, process};
+
+use echo2garmin::profile::{
+    Activity, ActivityMesg, DeviceInfoMesg, Event, EventMesg, EventType, File, FileIdMesg, LapMesg, Manufacturer,
+    RecordMesg, SessionMesg, Sport, SubSport,
+};
+use echo2garmin::schema::{
+    base_type_size, FieldValue, BASE_TYPE_FLOAT32, BASE_TYPE_FLOAT64, BASE_TYPE_SINT64, BASE_TYPE_UINT64, BASE_TYPE_UINT64Z,
+    FIT_UINT16_INVALID, FIT_UINT32_INVALID, FIT_UINT8_INVALID,
+};
+use echo2garmin::{
+    decode, write_activity, write_developer_data_id, write_device_info_message, write_echo_developer_data,
+    write_field_description, write_file_id_message, write_lap, write_mesg, write_record, write_session, write_start_event,
+    write_stop_event, ByteOrder, Clock, DecodeError, DeveloperDataIdMesg, DeveloperField, EchoMetric, FieldDescriptionMesg,
+    FitError, FitWriter, HeaderSize, Mesg, Record,
+};
+
+const TIMESTAMP: u32 = 1_000_000_000;
+const BYTE_ORDERS: [ByteOrder; 2] = [ByteOrder::LittleEndian, ByteOrder::BigEndian];
+
+fn encode(byte_order: ByteOrder, write: impl FnOnce(&mut FitWriter<Vec<u8>>) -> Result<(), FitError>) -> Vec<u8> {
+    let mut fit = FitWriter::buffered(Vec::new());
+    fit.set_byte_order(byte_order);
+    write(&mut fit).unwrap();
+    fit.finish().unwrap()
+}
+
+// Writes `mesg` on its own, decodes it and checks the definition against
+// the message's field table as well as the decoded values.
+fn assert_round_trip<M: Mesg + Debug + PartialEq>(mesg: &M) {
+    for byte_order in BYTE_ORDERS {
+        let data = encode(byte_order, |fit| write_mesg(fit, mesg, &[]));
+        let file = decode(&data).unwrap();
+
+        let Record::Definition(definition) = &file.records[0] else { panic!("no definition first") };
+        assert_eq!(definition.mesg_num, M::MESG_NUM);
+        assert_eq!(definition.big_endian, byte_order == ByteOrder::BigEndian);
+        // Fields left invalid are not written, so the definition holds a
+        // subset of the table, in table order.
+        let mut declared = M::FIELDS.iter();
+        for field in &definition.fields {
+            let entry = declared.find(|entry| entry.num == field.num);
+            let entry = entry.unwrap_or_else(|| panic!("field {} of message {} is not in its table", field.num, M::MESG_NUM));
+            assert_eq!(field.base_type, entry.base_type, "base type of {}", entry.name);
+            assert_eq!(field.size % base_type_size(field.base_type), 0, "size of {}", entry.name);
+        }
+
+        let decoded: Vec<M> = file.data_mesgs().filter_map(|mesg| mesg.decode()).collect();
+        assert_eq!(decoded.as_slice(), std::slice::from_ref(mesg), "{byte_order:?}");
+    }
+}
+
+#[test]
+fn file_id() {
+    assert_round_trip(&FileIdMesg {
+        type_: File::Activity.into(),
+        manufacturer: Manufacturer::Development.into(),
+        product: 1234,
+        serial_number: 0x1234_5678,
+        time_created: TIMESTAMP,
+        number: 7,
+        product_name: "Echo Bike".into(),
+    });
+}
+
+#[test]
+fn device_info() {
+    let mut mesg = DeviceInfoMesg {
+        timestamp: TIMESTAMP,
+        device_index: 0,
+        manufacturer: Manufacturer::Development.into(),
+        serial_number: 42,
+        product: 1,
+        hardware_version: 3,
+        cum_operating_time: 3600,
+        descriptor: "console".into(),
+        product_name: "Echo Bike".into(),
+        ..Default::default()
+    };
+    mesg.set_software_version(2.31).unwrap();
+    mesg.set_battery_voltage(3.7).unwrap();
+    assert_round_trip(&mesg);
+}
+
+#[test]
+fn event() {
+    assert_round_trip(&EventMesg {
+        timestamp: TIMESTAMP,
+        event: Event::Timer.into(),
+        event_type: EventType::Start.into(),
+        data: 0,
+        event_group: 1,
+        ..Default::default()
+    });
+}
+
+#[test]
+fn record() {
+    let mut mesg = RecordMesg {
+        timestamp: TIMESTAMP,
+        position_lat: -495_000_000,
+        position_long: 1_700_000_000,
+        heart_rate: 151,
+        cadence: 88,
+        power: 312,
+        compressed_speed_distance: [0x12, 0x34, 0x56],
+        temperature: -4,
+        speed_1s: [1, 2, 3, 4, 5],
+        ..Default::default()
+    };
+    mesg.set_speed(8.333).unwrap();
+    mesg.set_distance(12_345.67).unwrap();
+    mesg.set_altitude(-12.4).unwrap();
+    mesg.set_grade(-3.5).unwrap();
+    assert_round_trip(&mesg);
+}
+
+#[test]
+fn lap() {
+    let mut mesg = LapMesg {
+        message_index: 0,
+        timestamp: TIMESTAMP + 600,
+        event: Event::Lap.into(),
+        event_type: EventType::Stop.into(),
+        start_time: TIMESTAMP,
+        total_calories: 95,
+        avg_heart_rate: 140,
+        max_heart_rate: 171,
+        avg_power: 250,
+        max_power: 620,
+        sport: Sport::Cycling.into(),
+        ..Default::default()
+    };
+    mesg.set_total_elapsed_time(600.0).unwrap();
+    mesg.set_total_timer_time(598.5).unwrap();
+    mesg.set_total_distance(5_000.0).unwrap();
+    mesg.set_avg_speed(8.33).unwrap();
+    assert_round_trip(&mesg);
+}
+
+#[test]
+fn session() {
+    let mut mesg = SessionMesg {
+        timestamp: TIMESTAMP + 600,
+        event: Event::Session.into(),
+        event_type: EventType::Stop.into(),
+        start_time: TIMESTAMP,
+        sport: Sport::Cycling.into(),
+        sub_sport: SubSport::IndoorCycling.into(),
+        total_calories: 95,
+        avg_power: 250,
+        first_lap_index: 0,
+        num_laps: 1,
+        ..Default::default()
+    };
+    mesg.set_total_elapsed_time(600.0).unwrap();
+    mesg.set_total_timer_time(600.0).unwrap();
+    mesg.set_max_speed(14.2).unwrap();
+    assert_round_trip(&mesg);
+}
+
+#[test]
+fn activity() {
+    let mut mesg = ActivityMesg {
+        timestamp: TIMESTAMP + 600,
+        num_sessions: 1,
+        type_: Activity::Manual.into(),
+        event: Event::Activity.into(),
+        event_type: EventType::Stop.into(),
+        local_timestamp: TIMESTAMP + 600 + 3600,
+        ..Default::default()
+    };
+    mesg.set_total_timer_time(600.0).unwrap();
+    assert_round_trip(&mesg);
+}
+
+#[test]
+fn invalid_values() {
+    assert_round_trip(&RecordMesg::default());
+    assert_round_trip(&SessionMesg::default());
+
+    // Invalid fields are left out of the definition and read back invalid.
+    let record = RecordMesg { timestamp: TIMESTAMP, power: 250, heart_rate: FIT_UINT8_INVALID, ..Default::default() };
+    let data = encode(ByteOrder::BigEndian, |fit| write_mesg(fit, &record, &[]));
+    let file = decode(&data).unwrap();
+    let mesg = file.data_mesgs().next().unwrap();
+    let nums: Vec<u8> = mesg.fields.iter().map(|field| field.num).collect();
+    assert_eq!(nums, [253, 7]);
+    let decoded: RecordMesg = mesg.decode().unwrap();
+    assert_eq!(decoded.heart_rate, FIT_UINT8_INVALID);
+    assert_eq!(decoded.distance, FIT_UINT32_INVALID);
+    assert_eq!(decoded.temperature, i8::MAX);
+
+    // Other encoders do write invalid values out; they decode the same way.
+    let data = encode(ByteOrder::LittleEndian, |fit| {
+        fit.write_message_definition(0, &[0, 0, 20, 0, 2, 253, 4, 0x86, 7, 2, 0x84])?;
+        fit.write_message(0, &[0, 0xCA, 0x9A, 0x3B, 0xFF, 0xFF])
+    });
+    let decoded: RecordMesg = decode(&data).unwrap().data_mesgs().next().unwrap().decode().unwrap();
+    assert_eq!(decoded, RecordMesg { timestamp: TIMESTAMP, power: FIT_UINT16_INVALID, ..Default::default() });
+}
+
+#[test]
+fn compressed_timestamps() {
+    let records: Vec<RecordMesg> = [0, 1, 2, 31, 32, 40, 100]
+        .into_iter()
+        .map(|offset| RecordMesg { timestamp: TIMESTAMP + offset, power: offset as u16, ..Default::default() })
+        .collect();
+    for byte_order in BYTE_ORDERS {
+        let data = encode(byte_order, |fit| {
+            fit.set_compressed_timestamps(true);
+            records.iter().try_for_each(|record| write_mesg(fit, record, &[]))
+        });
+        let file = decode(&data).unwrap();
+        assert!(file.data_mesgs().any(|mesg| mesg.compressed_timestamp.is_some()));
+        let decoded: Vec<RecordMesg> = file.data_mesgs().filter_map(|mesg| mesg.decode()).collect();
+        assert_eq!(decoded, records);
+    }
+}
+
+#[test]
+fn activity_file_writers() {
+    for byte_order in BYTE_ORDERS {
+        let data = encode(byte_order, |fit| {
+            write_file_id_message(fit)?;
+            write_device_info_message(fit)?;
+            write_start_event(fit)?;
+            write_record(fit, &[])?;
+            write_stop_event(fit)?;
+            write_lap(fit, &[])?;
+            write_session(fit, &[])?;
+            write_activity(fit)
+        });
+        let file = decode(&data).unwrap();
+        let mesg_nums: Vec<u16> = file.data_mesgs().map(|mesg| mesg.mesg_num).collect();
+        assert_eq!(mesg_nums, [0, 23, 21, 20, 21, 19, 18, 34]);
+
+        let file_id: FileIdMesg = file.data_mesgs().next().unwrap().decode().unwrap();
+        assert_eq!(file_id.type_, u8::from(File::Activity));
+        let session: SessionMesg = file.data_mesgs().find_map(|mesg| mesg.decode()).unwrap();
+        assert_eq!((session.sport, session.num_laps), (Sport::Generic.into(), 1));
+    }
+}
+
+#[test]
+fn echo_metrics() {
+    let metrics = [EchoMetric::ConsoleCalories(512), EchoMetric::CaloriesPerMinute(12.34), EchoMetric::ProgramNumber(7), EchoMetric::FanRpm(1450)];
+    let developer_fields: Vec<DeveloperField> = metrics.into_iter().map(|metric| metric.try_into().unwrap()).collect();
+    for byte_order in BYTE_ORDERS {
+        let data = encode(byte_order, |fit| {
+            write_echo_developer_data(fit)?;
+            write_mesg(fit, &RecordMesg { timestamp: TIMESTAMP, ..Default::default() }, &developer_fields)
+        });
+        let file = decode(&data).unwrap();
+        let record = file.data_mesgs().last().unwrap();
+        let values: Vec<(u8, &[u8])> = record.developer_fields.iter().map(|field| (field.field_definition_number, &field.value[..])).collect();
+        assert_eq!(values, [(0, &512u16.to_le_bytes()[..]), (1, &123u16.to_le_bytes()), (2, &[7]), (3, &1450u16.to_le_bytes())]);
+    }
+
+    // Values the field cannot hold are refused rather than written as the
+    // invalid value or zero.
+    for metric in [
+        EchoMetric::ConsoleCalories(0xFFFF),
+        EchoMetric::CaloriesPerMinute(6553.5),
+        EchoMetric::CaloriesPerMinute(-1.0),
+        EchoMetric::CaloriesPerMinute(f64::NAN),
+        EchoMetric::ProgramNumber(0xFF),
+    ] {
+        assert!(DeveloperField::try_from(metric).is_err(), "{metric:?}");
+    }
+}
+
+// Little-endian encodings of a value of each base type added with protocol
+// 2.0, or that only it leaves room for, chosen to use every byte.
+fn wide_values() -> [(u8, Vec<u8>); 5] {
+    fn encoded<T: FieldValue>(value: T) -> Vec<u8> {
+        let mut data = Vec::new();
+        value.write_to(&mut data);
+        data
+    }
+    [
+        (BASE_TYPE_FLOAT32, encoded(-1234.5678f32)),
+        (BASE_TYPE_FLOAT64, encoded(std::f64::consts::PI * 1e100)),
+        (BASE_TYPE_SINT64, encoded(-0x0123_4567_89AB_CDEFi64)),
+        (BASE_TYPE_UINT64, encoded(0xFEDC_BA98_7654_3210u64)),
+        (BASE_TYPE_UINT64Z, encoded(0x0102_0304_0506_0708u64)),
+    ]
+}
+
+#[test]
+fn wide_developer_fields() {
+    let values = wide_values();
+    let developer_fields: Vec<DeveloperField> = values
+        .iter()
+        .enumerate()
+        .map(|(num, (_, value))| DeveloperField { developer_data_index: 0, field_definition_number: num as u8, value: value.clone() })
+        .collect();
+    for byte_order in BYTE_ORDERS {
+        let data = encode(byte_order, |fit| {
+            write_developer_data_id(fit, &DeveloperDataIdMesg { developer_data_index: 0, application_version: 1, ..Default::default() })?;
+            for (num, (base_type, _)) in values.iter().enumerate() {
+                write_field_description(fit, &FieldDescriptionMesg {
+                    developer_data_index: 0,
+                    field_definition_number: num as u8,
+                    fit_base_type_id: *base_type,
+                    field_name: format!("field_{num}").as_str().into(),
+                    ..Default::default()
+                })?;
+            }
+            write_mesg(fit, &RecordMesg { timestamp: TIMESTAMP, ..Default::default() }, &developer_fields)
+        });
+        let file = decode(&data).unwrap();
+        assert_eq!(file.header.protocol_version, 0x20);
+        let record = file.data_mesgs().last().unwrap();
+        assert_eq!(record.developer_fields, developer_fields, "{byte_order:?}");
+    }
+}
+
+#[test]
+fn wide_native_fields() {
+    // A manufacturer specific message with a single field of each type.
+    for (base_type, value) in wide_values() {
+        let mesg_def = [0, 0, 0x00, 0xFF, 1, 0, value.len() as u8, base_type];
+        for byte_order in BYTE_ORDERS {
+            let data = encode(byte_order, |fit| fit.write_mesg(&mesg_def, &value));
+            let file = decode(&data).unwrap();
+            let Record::Definition(definition) = &file.records[0] else { panic!("no definition first") };
+            assert_eq!(definition.big_endian, byte_order == ByteOrder::BigEndian);
+            let mesg = file.data_mesgs().next().unwrap();
+            assert_eq!(mesg.fields[0].value, value, "{base_type:#04x} {byte_order:?}");
+
+            // Floats were already part of protocol 1.0; 64-bit integers are not.
+            let protocol_version = if matches!(base_type, BASE_TYPE_FLOAT32 | BASE_TYPE_FLOAT64) { 0x10 } else { 0x20 };
+            assert_eq!(file.header.protocol_version, protocol_version, "{base_type:#04x}");
+        }
+    }
+}
+
+#[test]
+fn header_and_file_crc() {
+    for header_size in [HeaderSize::Short, HeaderSize::Long] {
+        let mut fit = FitWriter::with_header_size(Cursor::new(Vec::new()), header_size).unwrap();
+        write_file_id_message(&mut fit).unwrap();
+        let data = fit.finish().unwrap().into_inner();
+
+        let file = decode(&data).unwrap();
+        assert_eq!(file.header.header_size, header_size);
+        assert_eq!(file.header.data_size as usize, data.len() - header_size as usize - 2);
+        assert_eq!(file.header.crc.is_some(), header_size == HeaderSize::Long);
+
+        let mut corrupt = data.clone();
+        *corrupt.last_mut().unwrap() ^= 0x01;
+        assert!(matches!(decode(&corrupt), Err(DecodeError::CrcMismatch { .. })));
+    }
+
+    // The writer derives the file CRC from the header's and the record
+    // area's; the decoder recomputes it over the whole file, so any
+    // mistake in combining the two shows up for some length.
+    for header_size in [HeaderSize::Short, HeaderSize::Long] {
+        for size in 1..=255u8 {
+            let mut fit = FitWriter::with_header_size(Cursor::new(Vec::new()), header_size).unwrap();
+            for count in 0..size % 4 + 1 {
+                fit.write_mesg(&[0, 0, 0x00, 0xFF, 1, 0, size, 0x0D], &vec![count; size as usize]).unwrap();
+            }
+            let data = fit.finish().unwrap().into_inner();
+            decode(&data).unwrap_or_else(|error| panic!("{header_size:?}, {} data bytes: {error}", data.len()));
+        }
+    }
+
+    let data = encode(ByteOrder::LittleEndian, write_file_id_message);
+    let mut corrupt = data.clone();
+    corrupt[13] ^= 0x01;
+    assert!(matches!(decode(&corrupt), Err(DecodeError::HeaderCrcMismatch { .. })));
+    assert!(matches!(decode(&data[..data.len() - 1]), Err(DecodeError::UnexpectedEof { .. })));
+}
+
+#[test]
+fn output_sinks() {
+    // Enough records to go through the write buffer a few times.
+    fn write(fit: &mut FitWriter<impl std::io::Write>) {
+        fit.set_clock(Clock::Fixed(TIMESTAMP));
+        write_file_id_message(fit).unwrap();
+        for second in 0..20_000 {
+            write_mesg(fit, &RecordMesg { timestamp: TIMESTAMP + second, power: 200, ..Default::default() }, &[]).unwrap();
+        }
+    }
+
+    let mut fit = FitWriter::buffered(Vec::new());
+    write(&mut fit);
+    let buffered = fit.finish().unwrap();
+    decode(&buffered).unwrap();
+
+    let mut fit = FitWriter::new(Cursor::new(Vec::new())).unwrap();
+    write(&mut fit);
+    assert!(fit.finish().unwrap().into_inner() == buffered, "seekable");
+
+    let path = env::temp_dir().join(format!("echo2garmin-spool-{}", process::id()));
+    let spool = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+    let mut fit = FitWriter::spooled(Vec::new(), spool);
+    write(&mut fit);
+    let spooled = fit.finish();
+    fs::remove_file(&path).unwrap();
+    assert!(spooled.unwrap() == buffered, "spool file");
+
+    let mut fit = FitWriter::spooled(Vec::new(), Cursor::new(Vec::new()));
+    write(&mut fit);
+    assert!(fit.finish().unwrap() == buffered, "in-memory spool");
+}
@@ -0,0 +1,68 @@
+//! String fields: written as UTF-8 plus a terminator, cut to fit on a
+//! character boundary, and read back unchanged.
+
+use echo2garmin::profile::FileIdMesg;
+use echo2garmin::schema::BASE_TYPE_STRING;
+use echo2garmin::{decode, write_mesg, ByteOrder, FitString, FitWriter};
+
+// product_name in file_id.
+const PRODUCT_NAME: u8 = 8;
+
+// Writes a file_id message with `product_name` alone and returns the bytes
+// on disk for that field along with the name read back.
+fn write_product_name(product_name: &str, byte_order: ByteOrder) -> (Vec<u8>, String) {
+    let mut fit = FitWriter::buffered(Vec::new());
+    fit.set_byte_order(byte_order);
+    write_mesg(&mut fit, &FileIdMesg { product_name: product_name.into(), ..Default::default() }, &[]).unwrap();
+    let file = decode(&fit.finish().unwrap()).unwrap();
+
+    let mesg = file.data_mesgs().next().unwrap();
+    let field = mesg.fields.iter().find(|field| field.num == PRODUCT_NAME).unwrap();
+    assert_eq!(field.base_type, BASE_TYPE_STRING);
+    (field.value.clone(), mesg.decode::<FileIdMesg>().unwrap().product_name.as_str().to_string())
+}
+
+#[test]
+fn non_ascii_round_trip() {
+    let name = "Échobike – Garage";
+    for byte_order in [ByteOrder::LittleEndian, ByteOrder::BigEndian] {
+        let (raw, decoded) = write_product_name(name, byte_order);
+        assert_eq!(raw, [name.as_bytes(), &[0]].concat());
+        assert_eq!(decoded, name);
+    }
+}
+
+#[test]
+fn truncated_on_char_boundary() {
+    // 2-byte characters end on an even length; 254 bytes fit exactly.
+    let name = "é".repeat(200);
+    let (raw, decoded) = write_product_name(&name, ByteOrder::LittleEndian);
+    assert_eq!(raw.len(), FitString::MAX_LEN + 1);
+    assert_eq!(decoded, "é".repeat(127));
+
+    // With a one byte lead the 254th byte is the middle of an "é", so the
+    // string stops one byte short of the limit.
+    let name = format!("a{}", "é".repeat(200));
+    let (raw, decoded) = write_product_name(&name, ByteOrder::LittleEndian);
+    assert_eq!(raw.len(), FitString::MAX_LEN);
+    assert_eq!(decoded, format!("a{}", "é".repeat(126)));
+
+    // The 3-byte "–" straddling the limit is dropped whole.
+    let name = format!("{}–", "a".repeat(252));
+    assert_eq!(FitString::new(&name).as_str(), "a".repeat(252));
+    assert_eq!(FitString::new(&"a".repeat(300)).as_str().len(), FitString::MAX_LEN);
+}
+
+#[test]
+fn cut_at_embedded_nul() {
+    let (raw, decoded) = write_product_name("Echo\0Bike", ByteOrder::LittleEndian);
+    assert_eq!(raw, b"Echo\0");
+    assert_eq!(decoded, "Echo");
+
+    // Nothing before the null leaves the field unset, so it is not written.
+    assert_eq!(FitString::new("\0Echo").as_str(), "");
+    let mut fit = FitWriter::buffered(Vec::new());
+    write_mesg(&mut fit, &FileIdMesg { product_name: "\0Echo".into(), ..Default::default() }, &[]).unwrap();
+    let file = decode(&fit.finish().unwrap()).unwrap();
+    assert!(file.data_mesgs().next().unwrap().fields.iter().all(|field| field.num != PRODUCT_NAME));
+}
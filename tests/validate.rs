@@ -0,0 +1,174 @@
+//! The validator: files from the writers come out clean, and each kind of
+//! issue is reported at the byte it was found at.
+
+use echo2garmin::profile::{ActivityMesg, FileIdMesg, SessionMesg};
+use echo2garmin::{
+    validate, write_activity_file, write_mesg, ByteOrder, DecodeError, Decoder, FitWriter, Issue, IssueKind, Record,
+    RecordMesg,
+};
+
+mod common;
+use common::{encode, fit_file, PROTOCOL_VERSION_1};
+
+// A valid activity with records at the given times.
+fn activity(timestamps: &[u32]) -> Vec<u8> {
+    encode(|fit| {
+        write_mesg(fit, &FileIdMesg { product: 1, ..Default::default() }, &[])?;
+        for &timestamp in timestamps {
+            write_mesg(fit, &RecordMesg { timestamp, ..Default::default() }, &[])?;
+        }
+        let end = timestamps.iter().copied().max().unwrap_or(0);
+        write_mesg(fit, &SessionMesg { timestamp: end, num_laps: 1, ..Default::default() }, &[])?;
+        write_mesg(fit, &ActivityMesg { timestamp: end, num_sessions: 1, ..Default::default() }, &[])
+    })
+}
+
+// Offsets of the data messages, in file order.
+fn data_offsets(data: &[u8]) -> Vec<usize> {
+    let mut decoder = Decoder::new(data).unwrap();
+    let mut offsets = Vec::new();
+    loop {
+        let offset = decoder.offset();
+        match decoder.next_record().unwrap() {
+            Some(Record::Data(_)) => offsets.push(offset),
+            Some(Record::Definition(_)) => {}
+            None => return offsets,
+        }
+    }
+}
+
+#[test]
+fn activity_file_is_valid() {
+    for byte_order in [ByteOrder::LittleEndian, ByteOrder::BigEndian] {
+        for compressed_timestamps in [false, true] {
+            let mut fit = FitWriter::buffered(Vec::new());
+            fit.set_byte_order(byte_order);
+            fit.set_compressed_timestamps(compressed_timestamps);
+            write_activity_file(&mut fit).unwrap();
+            let data = fit.finish().unwrap();
+            assert_eq!(validate(&data), [], "{byte_order:?}, compressed timestamps {compressed_timestamps}");
+        }
+    }
+    assert_eq!(validate(&activity(&[100, 100, 101])), []);
+}
+
+#[test]
+fn header() {
+    let issues = validate(&[14, 0x10, 0xA6]);
+    assert!(matches!(issues[..], [Issue { offset: 0, kind: IssueKind::Decode(_) }]), "{issues:?}");
+
+    // The file CRC covers the header too, so it no longer matches either.
+    let mut data = activity(&[100]);
+    data[12] ^= 0x01;
+    let issues = validate(&data);
+    let end = data.len() - 2;
+    assert!(
+        matches!(
+            issues[..],
+            [
+                Issue { offset: 0, kind: IssueKind::Decode(DecodeError::HeaderCrcMismatch { .. }) },
+                Issue { offset, kind: IssueKind::Decode(DecodeError::CrcMismatch { .. }) },
+            ] if offset == end
+        ),
+        "{issues:?}"
+    );
+}
+
+#[test]
+fn file_length() {
+    let data = activity(&[100]);
+    let data_size = data.len() as u32 - 16;
+
+    let issues = validate(&data[..data.len() - 1]);
+    assert_eq!(issues[0], Issue { offset: 4, kind: IssueKind::Truncated { data_size, file_len: data.len() - 1 } });
+
+    let mut trailing = data.clone();
+    trailing.extend_from_slice(&[0x0E, 0x10, 0x00]);
+    assert_eq!(validate(&trailing), [Issue { offset: data.len(), kind: IssueKind::TrailingBytes { len: 3 } }]);
+
+    let mut corrupt = data.clone();
+    *corrupt.last_mut().unwrap() ^= 0x01;
+    let issues = validate(&corrupt);
+    assert!(matches!(issues[..], [Issue { kind: IssueKind::Decode(DecodeError::CrcMismatch { .. }), .. }]), "{issues:?}");
+    assert_eq!(issues[0].offset, data.len() - 2);
+}
+
+#[test]
+fn definitions() {
+    // A record definition with a field of unknown base type 0x55 and a
+    // uint16 power field 3 bytes long, followed by a data message.
+    let records = [0x40, 0, 0, 20, 0, 2, 3, 2, 0x55, 7, 3, 0x84, 0x00, 1, 2, 3, 4, 5];
+    let issues = validate(&fit_file(PROTOCOL_VERSION_1, &records));
+    assert_eq!(issues[0], Issue { offset: 14, kind: IssueKind::UnknownBaseType { mesg_num: 20, field_num: 3, base_type: 0x55 } });
+    assert_eq!(
+        issues[1],
+        Issue { offset: 14, kind: IssueKind::FieldSizeMismatch { mesg_num: 20, field_num: 7, size: 3, base_type: 0x84 } }
+    );
+}
+
+#[test]
+fn timestamps() {
+    let data = activity(&[100, 105, 103, 103, 110]);
+    let offsets = data_offsets(&data);
+    assert_eq!(validate(&data), [Issue { offset: offsets[3], kind: IssueKind::TimestampBackwards { timestamp: 103, previous: 105 } }]);
+
+    // Compressed timestamps count too.
+    let mut fit = FitWriter::buffered(Vec::new());
+    fit.set_compressed_timestamps(true);
+    write_mesg(&mut fit, &FileIdMesg { product: 1, ..Default::default() }, &[]).unwrap();
+    for timestamp in [1_000, 1_010, 1_005] {
+        write_mesg(&mut fit, &RecordMesg { timestamp, ..Default::default() }, &[]).unwrap();
+    }
+    let data = fit.finish().unwrap();
+    let offsets = data_offsets(&data);
+    let issues = validate(&data);
+    assert_eq!(issues[0], Issue { offset: offsets[3], kind: IssueKind::TimestampBackwards { timestamp: 1_005, previous: 1_010 } });
+
+    // Field 253 only counts as a timestamp when it is a uint32, as in the
+    // decoder; here it is four raw bytes.
+    let data = encode(|fit| {
+        write_mesg(fit, &FileIdMesg { product: 1, ..Default::default() }, &[])?;
+        write_mesg(fit, &RecordMesg { timestamp: 1_000, ..Default::default() }, &[])?;
+        fit.write_mesg(&[0, 0, 21, 0, 1, 253, 4, 0x0D], &[0, 0, 0, 0x7F])?;
+        write_mesg(fit, &SessionMesg { timestamp: 1_000, num_laps: 1, ..Default::default() }, &[])?;
+        write_mesg(fit, &ActivityMesg { timestamp: 1_000, num_sessions: 1, ..Default::default() }, &[])
+    });
+    assert_eq!(validate(&data), []);
+}
+
+#[test]
+fn message_order() {
+    let data = encode(|fit| {
+        write_mesg(fit, &RecordMesg { timestamp: 100, ..Default::default() }, &[])?;
+        write_mesg(fit, &FileIdMesg { product: 1, ..Default::default() }, &[])
+    });
+    let offsets = data_offsets(&data);
+    let end = data.len() - 2;
+    assert_eq!(
+        validate(&data),
+        [
+            Issue { offset: offsets[0], kind: IssueKind::FileIdNotFirst },
+            Issue { offset: offsets[1], kind: IssueKind::ActivityNotLast },
+            Issue { offset: end, kind: IssueKind::MissingSession },
+        ]
+    );
+
+    // No messages at all.
+    assert_eq!(
+        validate(&fit_file(PROTOCOL_VERSION_1, &[])),
+        [
+            Issue { offset: 14, kind: IssueKind::FileIdNotFirst },
+            Issue { offset: 14, kind: IssueKind::ActivityNotLast },
+            Issue { offset: 14, kind: IssueKind::MissingSession },
+        ]
+    );
+}
+
+#[test]
+fn stops_at_decode_error() {
+    // A data message on local type 3, which was never defined, then bytes
+    // that would otherwise read as another problem.
+    let records = [0x03, 0x40, 0, 0, 20, 0, 1, 3, 2, 0x55];
+    let issues = validate(&fit_file(PROTOCOL_VERSION_1, &records));
+    assert_eq!(issues, [Issue { offset: 14, kind: IssueKind::Decode(DecodeError::UndefinedLocalMesg { offset: 14, local_mesg_number: 3 }) }]);
+}